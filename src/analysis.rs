@@ -0,0 +1,496 @@
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    parse::apgcode,
+    quadtree::NodeRef,
+    universe::{InsertMode, Universe},
+};
+
+// patterns are only followed up to this population
+pub const MAX_ANALYSIS_POPULATION: u64 = 1 << 16;
+// objects larger than this are never taken for emitted spaceships
+const MAX_EMITTED_POPULATION: usize = 64;
+// generations an isolated object gets to show it is a spaceship
+const EMITTED_BUDGET: u64 = 256;
+// periods of steady linear growth needed before calling something a gun or puffer
+const MIN_GROWTH_PERIODS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Periodicity {
+    // generation the first repeated phase appears
+    pub start: u64,
+    pub period: u64,
+    pub dx: i64,
+    pub dy: i64,
+    pub population: u64,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl Periodicity {
+    pub fn is_spaceship(&self) -> bool {
+        self.population != 0 && (self.dx, self.dy) != (0, 0)
+    }
+    // speed in the usual c/n notation, e.g. c/4d for the glider and 2c/5o
+    pub fn speed(&self) -> Option<String> {
+        if !self.is_spaceship() {
+            return None;
+        }
+        let (dx, dy) = (self.dx.unsigned_abs(), self.dy.unsigned_abs());
+        let (a, b) = (dx.max(dy), dx.min(dy));
+        if b != 0 && a != b {
+            // oblique speeds keep the full displacement, e.g. (2,1)c/6
+            return Some(format!("({},{})c/{}", a, b, self.period));
+        }
+        let g = gcd(a, self.period);
+        let (distance, period) = (a / g, self.period / g);
+        let direction = if b == 0 { "o" } else { "d" };
+        Some(if distance == 1 {
+            format!("c/{}{}", period, direction)
+        } else {
+            format!("{}c/{}{}", distance, period, direction)
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PatternClass {
+    Dies { generation: u64 },
+    StillLife,
+    Oscillator { period: u64 },
+    Spaceship(Periodicity),
+    // a stationary part that sends out spaceships every period
+    Gun { period: u64, emitted: Periodicity },
+    // anything else whose population grows by the same amount every period,
+    // moving engines and rakes included
+    Puffer { period: u64 },
+    UnboundedGrowth,
+    Unknown,
+}
+
+impl From<Periodicity> for PatternClass {
+    fn from(p: Periodicity) -> Self {
+        if p.population == 0 {
+            Self::Dies {
+                generation: p.start,
+            }
+        } else if p.is_spaceship() {
+            Self::Spaceship(p)
+        } else if p.period == 1 {
+            Self::StillLife
+        } else {
+            Self::Oscillator { period: p.period }
+        }
+    }
+}
+
+impl fmt::Display for PatternClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dies { generation } => write!(f, "dies at generation {}", generation),
+            Self::StillLife => write!(f, "still life"),
+            Self::Oscillator { period } => write!(f, "p{} oscillator", period),
+            Self::Spaceship(p) => write!(f, "p{} spaceship, {}", p.period, p.speed().unwrap()),
+            Self::Gun { period, emitted } => write!(
+                f,
+                "p{} gun emitting {} spaceships",
+                period,
+                emitted.speed().unwrap()
+            ),
+            Self::Puffer { period } => write!(f, "p{} puffer", period),
+            Self::UnboundedGrowth => write!(f, "unbounded growth"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Classification {
+    pub class: PatternClass,
+    // 1.0 when a phase repeated, which settles it for good.
+    // guns and puffers get more certain the more periods of steady growth were seen
+    pub confidence: f64,
+    pub generations: u64,
+}
+
+// smallest period over which the population grew by the same positive amount
+// at the end of the run, and how many periods in a row it did so
+fn linear_growth(populations: &[u64]) -> Option<(u64, usize)> {
+    let n = populations.len() - 1;
+    let grows_by = |t: usize, p: usize| populations[t] as i64 - populations[t - p] as i64;
+    (1..=n / (MIN_GROWTH_PERIODS + 1)).find_map(|p| {
+        let d = grows_by(n, p);
+        if d <= 0 {
+            return None;
+        }
+        let steady = (p..=n).rev().take_while(|&t| grows_by(t, p) == d).count();
+        let periods = steady / p;
+        (periods >= MIN_GROWTH_PERIODS).then_some((p as u64, periods))
+    })
+}
+
+fn bounding_rect(cells: &[(i64, i64, u8)]) -> (i64, i64, i64, i64) {
+    cells.iter().fold(
+        (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+        |(x1, y1, x2, y2), &(x, y, _)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+    )
+}
+
+// the cells relative to their bounding box in row-major order, so translated copies are equal
+fn normalized(cells: &[(i64, i64, u8)]) -> Vec<(i64, i64, u8)> {
+    let (x1, y1, _, _) = bounding_rect(cells);
+    let mut cells = cells
+        .iter()
+        .map(|&(x, y, state)| (x - x1, y - y1, state))
+        .collect::<Vec<_>>();
+    cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+    cells
+}
+
+// live cells grouped so that cells up to `distance` apart end up together
+fn clusters(cells: &[(i64, i64, u8)], distance: i64) -> Vec<Vec<(i64, i64, u8)>> {
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let index = cells
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y, _))| ((x, y), i))
+        .collect::<FxHashMap<_, _>>();
+    let mut parent = (0..cells.len()).collect::<Vec<_>>();
+    for (i, &(x, y, _)) in cells.iter().enumerate() {
+        let offsets =
+            (-distance..=distance).flat_map(|dx| (-distance..=distance).map(move |dy| (dx, dy)));
+        for (dx, dy) in offsets {
+            if let Some(&j) = index.get(&(x + dx, y + dy)) {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+    let mut groups = FxHashMap::<usize, Vec<_>>::default();
+    for (i, &cell) in cells.iter().enumerate() {
+        groups.entry(find(&mut parent, i)).or_default().push(cell);
+    }
+    groups.into_values().collect()
+}
+
+impl Universe {
+    // (population, width, height) and top-left corner of the pattern, a cheap stand-in
+    // for its shape that only needs the cells hashed when two generations agree on it
+    fn phase_outline(&self) -> ((u64, i64, i64), i64, i64) {
+        let population = self.population();
+        if population == 0 {
+            return ((0, 0, 0), 0, 0);
+        }
+        let (x1, y1, x2, y2) = self.get_bounding_rect();
+        ((population, x2 - x1, y2 - y1), x1, y1)
+    }
+    fn normalize_phase(
+        &mut self,
+        root: NodeRef,
+        phases: &mut FxHashMap<NodeRef, Vec<(i64, i64, u8)>>,
+    ) {
+        phases.entry(root).or_insert_with(|| {
+            let current = std::mem::replace(self.root_mut(), root);
+            let cells = normalized(&self.iter_alive().collect::<Vec<_>>());
+            *self.root_mut() = current;
+            cells
+        });
+    }
+
+    // live cells grouped into objects, cells up to two apart belong to the same one
+    pub fn objects(&self) -> Vec<Vec<(i64, i64, u8)>> {
+        clusters(&self.iter_alive().collect::<Vec<_>>(), 2)
+    }
+
+    // runs f on the cells alone, then puts the pattern back
+    fn isolated<R>(&mut self, cells: &[(i64, i64, u8)], f: impl FnOnce(&mut Self) -> R) -> R {
+        self.restoring(|u| {
+            let (x1, y1, x2, y2) = bounding_rect(cells);
+            u.clear();
+            u.set_points(cells, x1, y1, x2, y2, &InsertMode::Or);
+            f(u)
+        })
+    }
+    fn evolve_alone(
+        &mut self,
+        cells: &[(i64, i64, u8)],
+        generations: u64,
+    ) -> Vec<Vec<(i64, i64, u8)>> {
+        self.isolated(cells, |u| {
+            (0..generations)
+                .map(|_| {
+                    let mut cells = u.iter_alive().collect::<Vec<_>>();
+                    cells.sort_unstable();
+                    u.step();
+                    cells
+                })
+                .collect()
+        })
+    }
+
+    // splits a periodic object into the smallest groups of connected cells
+    // that evolve over a full period just as they do together
+    fn separate(
+        &mut self,
+        object: Vec<(i64, i64, u8)>,
+        max_period: u64,
+    ) -> Vec<Vec<(i64, i64, u8)>> {
+        let mut groups = clusters(&object, 1);
+        if groups.len() == 1 {
+            return groups;
+        }
+        let generation = self.generation;
+        let Some(p) = self
+            .isolated(&object, |u| u.track_period(max_period, &mut Vec::new()))
+            .filter(|p| p.start == generation)
+        else {
+            return vec![object];
+        };
+        // one generation past the period, or a still life would only compare its starting cells
+        let generations = (p.period + 1).max(2);
+        let joint = self.evolve_alone(&object, generations);
+
+        loop {
+            let evolutions = groups
+                .iter()
+                .map(|group| self.evolve_alone(group, generations))
+                .collect::<Vec<_>>();
+            let independent = joint.iter().enumerate().all(|(t, cells)| {
+                let mut union = evolutions
+                    .iter()
+                    .flat_map(|e| e[t].clone())
+                    .collect::<Vec<_>>();
+                union.sort_unstable();
+                union == *cells
+            });
+            if independent {
+                return groups;
+            }
+            // groups that come within reach of each other interact
+            let near = |a: &[(i64, i64, u8)], b: &[(i64, i64, u8)]| {
+                a.iter().any(|&(x1, y1, _)| {
+                    b.iter()
+                        .any(|&(x2, y2, _)| (x1 - x2).abs() <= 2 && (y1 - y2).abs() <= 2)
+                })
+            };
+            let pair = (0..groups.len())
+                .flat_map(|i| (i + 1..groups.len()).map(move |j| (i, j)))
+                .find(|&(i, j)| {
+                    (0..generations as usize).any(|t| near(&evolutions[i][t], &evolutions[j][t]))
+                });
+            let Some((i, j)) = pair else {
+                return vec![object];
+            };
+            let merged = groups.swap_remove(j);
+            groups[i].extend(merged);
+        }
+    }
+
+    // objects split further into independent parts, for patterns that have settled
+    pub fn separated_objects(&mut self, max_period: u64) -> Vec<Vec<(i64, i64, u8)>> {
+        self.objects()
+            .into_iter()
+            .flat_map(|object| self.separate(object, max_period))
+            .collect()
+    }
+
+    // runs f and then puts the pattern back where it was
+    fn restoring<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let (root, generation, step) = (self.root(), self.generation, self.step);
+        let timeline = std::mem::take(&mut self.timeline);
+        // collecting would move the saved roots, so hold it off until the next real step
+        let budget = std::mem::replace(&mut self.arena_budget, usize::MAX);
+        self.step = 0;
+
+        let result = f(self);
+
+        *self.root_mut() = root;
+        self.generation = generation;
+        self.step = step;
+        self.timeline = timeline;
+        self.arena_budget = budget;
+        result
+    }
+
+    // steps one generation at a time until a phase repeats,
+    // recording the population of every generation on the way
+    fn track_period(
+        &mut self,
+        max_generations: u64,
+        populations: &mut Vec<u64>,
+    ) -> Option<Periodicity> {
+        let generation = self.generation;
+        let mut seen = FxHashMap::<_, Vec<_>>::default();
+        let mut normalized_phases = FxHashMap::default();
+        for g in 0..=max_generations {
+            let (outline, x, y) = self.phase_outline();
+            let (population, root) = (outline.0, self.root());
+            populations.push(population);
+            let phases = seen.entry(outline).or_default();
+            // the roots stay valid since collecting is held off while analyzing
+            let phases = std::mem::take(phases);
+            let repeat = phases.iter().find(|&&(_, _, _, seen_root)| {
+                self.normalize_phase(seen_root, &mut normalized_phases);
+                self.normalize_phase(root, &mut normalized_phases);
+                normalized_phases[&seen_root] == normalized_phases[&root]
+            });
+            if let Some(&(start, sx, sy, _)) = repeat {
+                return Some(Periodicity {
+                    start: generation + start,
+                    period: g - start,
+                    dx: x - sx,
+                    dy: y - sy,
+                    population,
+                });
+            }
+            if population > MAX_ANALYSIS_POPULATION || g == max_generations {
+                break;
+            }
+            let mut phases = phases;
+            phases.push((g, x, y, root));
+            seen.insert(outline, phases);
+            self.step();
+        }
+        None
+    }
+
+    // runs the pattern until a phase repeats, then puts it back where it started
+    pub fn find_period(&mut self, max_generations: u64) -> Option<Periodicity> {
+        self.restoring(|u| u.track_period(max_generations, &mut Vec::new()))
+    }
+
+    // canonical apgcode of the cycle the pattern settles into, None if it dies or never repeats
+    pub fn apgcode(&mut self, max_generations: u64) -> Option<String> {
+        let p = self.find_period(max_generations)?;
+        self.apgcode_for(p)
+    }
+    // same as apgcode, with the period already found
+    pub fn apgcode_for(&mut self, p: Periodicity) -> Option<String> {
+        let prefix = match PatternClass::from(p) {
+            PatternClass::Dies { .. } => return None,
+            PatternClass::StillLife => format!("xs{}", p.population),
+            PatternClass::Oscillator { period } => format!("xp{}", period),
+            _ => format!("xq{}", p.period),
+        };
+        let phases = self.restoring(|u| {
+            u.advance_to(p.start);
+            (0..p.period)
+                .map(|_| {
+                    let cells = u.iter_alive().map(|(x, y, _)| (x, y)).collect();
+                    u.step();
+                    cells
+                })
+                .collect::<Vec<_>>()
+        });
+        Some(format!("{}_{}", prefix, apgcode::canonical(&phases)))
+    }
+    pub fn apgcode_in_rect(
+        &mut self,
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+        max_generations: u64,
+    ) -> Option<String> {
+        let cells = self.iter_alive_in_rect(x1, y1, x2, y2).collect::<Vec<_>>();
+        self.isolated(&cells, |u| u.apgcode(max_generations))
+    }
+
+    // cells left after taking out every object that flies off on its own,
+    // along with one of the spaceships that were taken out
+    fn stationary_part(
+        &mut self,
+        spaceships: &mut FxHashMap<Vec<(i64, i64, u8)>, Option<Periodicity>>,
+    ) -> (Vec<(i64, i64, u8)>, Option<Periodicity>) {
+        let cells = self.iter_alive().collect::<Vec<_>>();
+        let mut remainder = Vec::new();
+        let mut emitted = None;
+        for object in clusters(&cells, 2) {
+            let spaceship = if object.len() <= MAX_EMITTED_POPULATION {
+                *spaceships.entry(normalized(&object)).or_insert_with(|| {
+                    self.restoring(|u| {
+                        let (x1, y1, x2, y2) = bounding_rect(&object);
+                        let generation = u.generation;
+                        u.clear();
+                        u.set_points(&object, x1, y1, x2, y2, &InsertMode::Or);
+                        u.track_period(EMITTED_BUDGET, &mut Vec::new())
+                            .filter(|p| p.is_spaceship() && p.start == generation)
+                    })
+                })
+            } else {
+                None
+            };
+            match spaceship {
+                Some(p) => emitted = emitted.or(Some(p)),
+                None => remainder.extend(object),
+            }
+        }
+        remainder.sort_unstable_by_key(|&(x, y, _)| (y, x));
+        (remainder, emitted)
+    }
+
+    // labels the pattern by running it for up to max_generations,
+    // plus one more period to tell guns from puffers
+    pub fn classify(&mut self, max_generations: u64) -> Classification {
+        self.restoring(|u| {
+            let start = u.generation;
+            let mut populations = Vec::new();
+            if let Some(p) = u.track_period(max_generations, &mut populations) {
+                return Classification {
+                    class: p.into(),
+                    confidence: 1.0,
+                    generations: u.generation - start,
+                };
+            }
+
+            let Some((period, periods)) = linear_growth(&populations) else {
+                let last = *populations.last().unwrap();
+                let half = populations[populations.len() / 2];
+                let confidence = if last > MAX_ANALYSIS_POPULATION {
+                    0.75
+                } else if last >= 2 * half.max(1) {
+                    0.5
+                } else {
+                    0.0
+                };
+                return Classification {
+                    class: if confidence > 0.0 {
+                        PatternClass::UnboundedGrowth
+                    } else {
+                        PatternClass::Unknown
+                    },
+                    confidence,
+                    generations: u.generation - start,
+                };
+            };
+
+            let mut spaceships = FxHashMap::default();
+            let (before, emitted) = u.stationary_part(&mut spaceships);
+            for _ in 0..period {
+                u.step();
+            }
+            let (after, _) = u.stationary_part(&mut spaceships);
+            let class = match emitted {
+                Some(emitted) if !before.is_empty() && before == after => {
+                    PatternClass::Gun { period, emitted }
+                }
+                _ => PatternClass::Puffer { period },
+            };
+            Classification {
+                class,
+                confidence: 1.0 - 1.0 / periods as f64,
+                generations: u.generation - start,
+            }
+        })
+    }
+}
@@ -1,19 +1,35 @@
 use crate::{
     components::{
-        AppMenu, ClipboardContext, Controls, Layer, PasteLayer, SelectionLayer, SelectionOverlay,
-        Stage, Status, use_toast,
+        AppMenu, ClipboardContext, Controls, Layer, PasteLayer, RestorePrompt, SelectionLayer,
+        SelectionOverlay, Stage, Status, use_toast,
     },
     draw::{self, Viewport},
+    history::Action,
     meta::use_metapixels,
-    parse::rle::{self, PatternMetadata},
-    universe::{InsertMode, Universe},
-    utils::str_from_base64_gz,
+    parse::{
+        self,
+        rle::{self, ParseMode, ParsedRle, PatternMetadata},
+    },
+    record::Recorder,
+    rule::{Rule, parse_rule},
+    session::{Session, SessionError},
+    universe::{InsertMode, MIN_LEVEL, Universe},
+    utils::{base64_gz_from_str, download_file, local_storage, str_from_base64_gz},
 };
 use gloo_net::http::Request;
-use leptos::{ev::mousedown, html, logging, prelude::*};
+use leptos::{ev::mousedown, html, logging, prelude::*, task::spawn_local};
 use leptos_router::hooks::*;
 use leptos_router::params::Params;
-use leptos_use::{UseClipboardReturn, use_clipboard, use_document, use_event_listener};
+use leptos_use::{
+    DebounceOptions, UseClipboardReturn, use_clipboard, use_debounce_fn_with_options, use_document,
+    use_event_listener,
+};
+
+// localStorage key of the autosaved session, kept gzipped and base64 encoded
+pub const SESSION_KEY: &str = "session";
+// ms of quiet before autosaving, and the longest a running pattern goes unsaved
+const AUTOSAVE_DELAY: f64 = 1000.0;
+const AUTOSAVE_MAX_WAIT: f64 = 10000.0;
 
 #[derive(Params, PartialEq, Clone)]
 pub struct GolParams {
@@ -30,6 +46,83 @@ pub struct GolContext {
     pub selection_rect: Signal<Option<(i64, i64, i64, i64)>, LocalStorage>,
     pub is_ticking: RwSignal<bool, LocalStorage>,
     pub tps: RwSignal<f64, LocalStorage>,
+    pub recorder: RwSignal<Option<Recorder>, LocalStorage>,
+    pub run_start: RwSignal<Option<RunStart>, LocalStorage>,
+}
+
+// where the current run began, what reset goes back to
+#[derive(Clone)]
+pub struct RunStart {
+    // index into Universe::history
+    pub snapshot: usize,
+    pub viewport: Viewport,
+    // ms since the epoch
+    pub time: f64,
+}
+
+impl GolContext {
+    // downloads whatever was recorded and stops the run it was following
+    pub fn finish_recording(&self) {
+        if let Some(recorder) = self.recorder.try_update(Option::take).flatten() {
+            self.is_ticking.set(false);
+            download_file(
+                &format!("{}.gif", self.name.get_untracked()),
+                &recorder.finish(),
+            );
+        }
+    }
+
+    // call before running the universe. a run from an edited pattern becomes the one reset returns to
+    pub fn begin_run(&self) {
+        let (is_edited, snapshot) = self
+            .universe
+            .with_untracked(|u| (u.is_edited(), u.history.current));
+        if is_edited {
+            self.run_start.set(Some(RunStart {
+                snapshot,
+                viewport: self.viewport.get_untracked(),
+                time: js_sys::Date::now(),
+            }));
+        }
+        self.universe.update(|u| u.push_snapshot(Action::Run));
+    }
+    pub fn reset(&self) {
+        let Some(RunStart {
+            snapshot, viewport, ..
+        }) = self.run_start.get_untracked()
+        else {
+            return;
+        };
+        self.is_ticking.set(false);
+        self.universe.update(|u| u.checkout(snapshot));
+        self.viewport.set(viewport);
+    }
+
+    pub fn session(&self) -> Session {
+        self.universe.with_untracked(|u| {
+            self.viewport.with_untracked(|vp| {
+                Session::new(u, &self.name.get_untracked(), self.tps.get_untracked(), vp)
+            })
+        })
+    }
+    // leaves everything as it was if the session can't be restored
+    pub fn restore_session(&self, session: &Session) -> Result<(), SessionError> {
+        self.universe
+            .try_update(|u| session.restore(u))
+            .unwrap_or(Ok(()))?;
+        self.name.set(session.name.clone());
+        self.tps.set(session.tps);
+        self.viewport.set(session.viewport.clone());
+        // the history it pointed into is gone
+        self.run_start.set(None);
+        Ok(())
+    }
+}
+
+pub async fn load_autosaved_session() -> Option<Session> {
+    let data = local_storage()?.get_item(SESSION_KEY).ok()??;
+    let json = str_from_base64_gz(&data).await.ok()?;
+    Session::from_json(&json).ok()
 }
 
 // TODO: impl GolContext
@@ -72,12 +165,14 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
     let location = use_location();
     let params = use_params::<GolParams>();
 
-    let universe = RwSignal::new_local(Universe::with_size_and_arena_capacity(60, 1 << 16));
+    let universe = RwSignal::new_local(Universe::with_size_and_arena_capacity(MIN_LEVEL, 1 << 16));
     let (canvas_size, set_canvas_size) = signal_local((0, 0));
     let viewport = RwSignal::new_local(Viewport::new());
     let cursor = RwSignal::new_local((0.0, 0.0));
     let is_ticking = RwSignal::new_local(false);
     let tps = RwSignal::new_local(16.0);
+    let recorder = RwSignal::new_local(None::<Recorder>);
+    let run_start = RwSignal::new_local(None::<RunStart>);
     let offset_to_world = move |x: i32, y: i32| viewport.with(|vp| vp.to_world_coords(x, y));
     let pan = StoredValue::<Option<(f64, f64)>>::new(None);
 
@@ -105,6 +200,8 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
         selection_rect,
         is_ticking,
         tps,
+        recorder,
+        run_start,
     };
     provide_context(ctx);
 
@@ -138,10 +235,32 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
             return;
         }
         if let Some(rle) = param_rle.or(hash_rle.get().unwrap())
-            && rle::parse_metadata(&rle, "", "").is_ok()
+            && let Ok((
+                PatternMetadata {
+                    rule,
+                    width,
+                    height,
+                    ..
+                },
+                _,
+            )) = rle::parse_metadata(&rle, "", "")
         {
+            let (rule, grid) = parse_rule(&rule).unwrap_or_else(|| {
+                logging.error(&format!(
+                    "Unsupported rule {}, running as {}",
+                    rule,
+                    Rule::LIFE
+                ));
+                (Rule::LIFE, None)
+            });
             universe.update(|u| {
                 u.clear();
+                // metapixels are built for B3/S23 regardless of the rule they emulate
+                (u.rule, u.grid) = if meta {
+                    (Rule::LIFE, None)
+                } else {
+                    (rule, grid)
+                };
                 if meta {
                     if let Some((Ok(on_rle), Ok(off_rle))) = metapixels.get() {
                         let rect = rle::to_grid(&rle).unwrap();
@@ -149,8 +268,7 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
                     }
                 } else {
                     let points = rle::iter_alive(&rle).unwrap().collect::<Vec<_>>();
-                    let half = 1i64 << (u.level() - 1);
-                    u.set_points(&points, -half, -half, half - 1, half - 1, &InsertMode::Copy);
+                    u.load_cells(&points, width, height);
                 }
             });
 
@@ -159,6 +277,34 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
         }
     });
 
+    // the autosaved session is only overwritten once the user chose whether to restore it
+    let is_autosaving = StoredValue::new_local(false);
+    let autosave = use_debounce_fn_with_options(
+        move || {
+            let json = ctx.session().to_json();
+            spawn_local(async move {
+                let (Some(storage), Ok(data)) = (local_storage(), base64_gz_from_str(&json).await)
+                else {
+                    return;
+                };
+                if storage.set_item(SESSION_KEY, &data).is_err() {
+                    logging::warn!("session too large to autosave");
+                }
+            });
+        },
+        AUTOSAVE_DELAY,
+        DebounceOptions::default().max_wait(Some(AUTOSAVE_MAX_WAIT)),
+    );
+    Effect::new(move |_| {
+        universe.track();
+        viewport.track();
+        tps.track();
+        name.track();
+        if is_autosaving.get_value() {
+            autosave();
+        }
+    });
+
     let is_canvas_dirty = StoredValue::new_local(true);
     Effect::new(move |_| {
         universe.track();
@@ -171,9 +317,7 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
     Effect::new(move |_| {
         is_ticking.track();
         if is_ticking.get() {
-            universe.update(|u| {
-                u.push_snapshot();
-            });
+            ctx.begin_run();
         }
         prev_tick.set_value(0.0);
     });
@@ -206,20 +350,33 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
     let copy_selection_rle = move || {
         if let Some((x1, y1, x2, y2)) = selection_rect.get() {
             universe.with(|u| {
-                let rle = rle::from_iter(u.iter_alive_in_rect(x1, y1, x2, y2), x1, y1, x2, y2);
+                let rle = rle::from_iter(
+                    u.iter_alive_in_rect(x1, y1, x2, y2),
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    &u.rule,
+                    u.grid.as_ref(),
+                );
                 copy(&rle);
                 logging.log("Copied RLE to clipboard!");
             });
         }
     };
     let paste = move || {
-        if let Ok(points) = rle::iter_alive(&paste_rle.get_value()) {
+        if let Ok(ParsedRle { cells, .. }) =
+            parse::parse(&paste_rle.get_value(), ParseMode::Lenient)
+        {
             let (cx, cy) = cursor.with(|(x, y)| (x.floor() as i64, y.floor() as i64));
             let (width, height) = paste_size.get();
             universe.update(|u| {
-                u.push_snapshot();
+                u.push_snapshot(Action::Paste);
                 u.set_points(
-                    &points.map(|(x, y)| (x + cx, y + cy)).collect::<Vec<_>>(),
+                    &cells
+                        .into_iter()
+                        .map(|(x, y, state)| (x + cx, y + cy, state))
+                        .collect::<Vec<_>>(),
                     cx,
                     cy,
                     cx + width - 1,
@@ -236,27 +393,24 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
             return;
         }
         paste_rle.set_value(rle.to_owned());
-        if let Ok(points) = rle::iter_alive(rle) {
+        // clipboard text can be in any of the plain text formats
+        if let Ok(ParsedRle {
+            metadata: PatternMetadata { width, height, .. },
+            cells,
+            ..
+        }) = parse::parse(rle, ParseMode::Lenient)
+        {
             paste_universe.update(|u| {
                 let half = 1i64 << (u.level() - 1);
-                u.set_points(
-                    &points.collect::<Vec<_>>(),
-                    -half,
-                    -half,
-                    half - 1,
-                    half - 1,
-                    &InsertMode::Copy,
-                );
+                u.set_points(&cells, -half, -half, half - 1, half - 1, &InsertMode::Copy);
             });
-        }
-        if let Ok((PatternMetadata { width, height, .. }, _)) = rle::parse_metadata(rle, "", "") {
             paste_size.set((width as i64, height as i64));
         }
     };
     let toggle_cell = move |x: i64, y: i64| {
         universe.update(|u| {
             let v = u.get(x, y);
-            u.set(x, y, v ^ 1);
+            u.set(x, y, (v == 0) as u8);
         });
     };
 
@@ -296,7 +450,7 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
                             } else if viewport.get().cell_size >= 5.0 {
                                 universe
                                     .update(|u| {
-                                        u.push_snapshot();
+                                        u.push_snapshot(Action::Toggle);
                                     });
                                 toggle_cell(x.floor() as i64, y.floor() as i64);
                             }
@@ -392,10 +546,14 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
                             if let Some((x1, y1, x2, y2)) = selection_rect.get() {
                                 universe
                                     .update(|u| {
+                                        u.push_snapshot(Action::Clear);
                                         u.clear_rect(x1, y1, x2, y2);
                                     });
                             }
                         }
+                        ("r", false) => {
+                            ctx.reset();
+                        }
                         ("Escape", _) => {
                             set_selection_start.set(None);
                             set_selection_end.set(None);
@@ -425,6 +583,21 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
                                     u.step();
                                 });
                             prev_tick.set_value(now);
+                            let is_recorded = recorder
+                                .try_update(|r| {
+                                    let r = r.as_mut()?;
+                                    Some(
+                                        universe
+                                            .with_untracked(|u| {
+                                                viewport.with_untracked(|vp| r.capture(u, vp))
+                                            }),
+                                    )
+                                })
+                                .flatten();
+                            if is_recorded == Some(true) {
+                                ctx.finish_recording();
+                                logging.log("Recording saved!");
+                            }
                         }
                         if !is_canvas_dirty.get_value() {
                             return;
@@ -442,6 +615,12 @@ pub fn App(#[prop(optional, into)] meta: bool) -> impl IntoView {
                 </Stage>
             </div>
             <SelectionOverlay is_open=is_selection_menu_shown />
+            <RestorePrompt on_close=move |is_restored| {
+                if is_restored {
+                    did_fit.set_value(true);
+                }
+                is_autosaving.set_value(true);
+            } />
             <div
                 on:click=|e| e.stop_propagation()
                 class="flex flex-col z-10 absolute bottom-0 inset-x-0 gap-4 pointer-events-none"
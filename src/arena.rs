@@ -41,4 +41,36 @@ where
     pub fn get(&self, index: Size) -> &T {
         self.vec.get(index as usize).unwrap()
     }
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+    // keeps only marked values, returning where each old index moved to.
+    // values may only refer to values inserted before them,
+    // so `remap` always sees the new index of anything it points to
+    pub fn compact(
+        &mut self,
+        marked: &[bool],
+        mut remap: impl FnMut(&mut T, &[Option<Size>]),
+    ) -> Vec<Option<Size>> {
+        let mut moved = vec![None; self.vec.len()];
+        let old = std::mem::take(&mut self.vec);
+        self.cache.clear();
+        self.size = 0;
+        for (i, mut value) in old.into_iter().enumerate() {
+            if !marked[i] {
+                continue;
+            }
+            remap(&mut value, &moved);
+            moved[i] = Some(self.size);
+            self.cache.insert(value.clone().into(), self.size);
+            self.vec.push(value);
+            self.size += 1;
+        }
+        self.cache.shrink_to_fit();
+        self.vec.shrink_to_fit();
+        moved
+    }
 }
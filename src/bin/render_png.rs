@@ -0,0 +1,123 @@
+use gol::{
+    draw::{self, Colors},
+    parse::{self, rle::ParseMode},
+    universe::Universe,
+};
+use std::{env, fs, path::Path};
+
+const USAGE: &str = "usage: render_png INPUT [--output FILE] [--generation N] [--cell-size PX] \
+                     [--rect X1,Y1,X2,Y2]";
+
+struct Options {
+    input: String,
+    output: Option<String>,
+    generation: u64,
+    cell_size: f64,
+    rect: Option<(i64, i64, i64, i64)>,
+}
+
+fn parse_rect(s: &str) -> Option<(i64, i64, i64, i64)> {
+    let mut parts = s.split(',').map(|part| part.trim().parse().ok());
+    let rect = (
+        parts.next()??,
+        parts.next()??,
+        parts.next()??,
+        parts.next()??,
+    );
+    (parts.next().is_none() && rect.0 <= rect.2 && rect.1 <= rect.3).then_some(rect)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut input = None;
+    let mut options = Options {
+        input: String::new(),
+        output: None,
+        generation: 0,
+        cell_size: draw::IMAGE_CELL_SIZE,
+        rect: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--output" => options.output = Some(value()?),
+            "--generation" => {
+                options.generation = value()?.parse().map_err(|_| "invalid generation")?
+            }
+            "--cell-size" => {
+                options.cell_size = value()?
+                    .parse()
+                    .ok()
+                    .filter(|&size: &f64| size > 0.0)
+                    .ok_or("invalid cell size")?
+            }
+            "--rect" => options.rect = Some(parse_rect(&value()?).ok_or("invalid rect")?),
+            "--help" | "-h" => return Err(USAGE.to_owned()),
+            _ if !arg.starts_with('-') && input.is_none() => input = Some(arg),
+            _ => return Err(format!("unknown argument: {}\n{}", arg, USAGE)),
+        }
+    }
+    options.input = input.ok_or(USAGE)?;
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    let text = match fs::read_to_string(&options.input) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", options.input, e);
+            std::process::exit(1);
+        }
+    };
+    let mut u = Universe::default();
+    match parse::load_universe(&text, ParseMode::Lenient, &mut u) {
+        Ok(warnings) => {
+            for warning in &warnings {
+                eprintln!("warning: {}", warning);
+            }
+        }
+        Err(message) => {
+            eprintln!("{}: {}", options.input, message);
+            std::process::exit(1);
+        }
+    }
+    u.advance_to(options.generation);
+
+    // an empty pattern has no bounding rect, so it becomes a 1x1 background image
+    let is_empty = options.rect.is_none() && u.population() == 0;
+    let (x1, y1, x2, y2) = match options.rect {
+        Some(rect) => rect,
+        None if is_empty => (0, 0, 0, 0),
+        None => u.get_bounding_rect(),
+    };
+    let cell_size = if is_empty {
+        1.0
+    } else {
+        draw::fit_cell_size(
+            x2 - x1 + 1,
+            y2 - y1 + 1,
+            options.cell_size,
+            draw::MAX_IMAGE_SIZE,
+        )
+    };
+    let canvas = draw::render_rect(&u, x1, y1, x2, y2, cell_size, &Colors::default());
+    let output = options.output.unwrap_or_else(|| {
+        Path::new(&options.input)
+            .with_extension("png")
+            .to_string_lossy()
+            .into_owned()
+    });
+    fs::write(&output, canvas.to_png()).unwrap();
+    eprintln!(
+        "wrote {}x{} image of generation {} to {}",
+        canvas.width, canvas.height, u.generation, output
+    );
+}
@@ -0,0 +1,317 @@
+use gol::{
+    analysis::PatternClass,
+    parse::apgcode,
+    rule::{Rule, parse_rule},
+    universe::{InsertMode, Universe},
+};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+const USAGE: &str = "usage: soup_search [--seed ROOT] [--soups N] [--rule RULE] [--threads N] \
+                     [--format json|haul] [--output FILE]";
+
+// soups are random 16x16 squares, like apgsearch's C1 symmetry
+const SOUP_SIZE: i64 = 16;
+// generations run between checks for stabilization
+const STABILIZE_CHUNK: u64 = 1 << 9;
+const MAX_GENERATIONS: u64 = 1 << 16;
+// a soup counts as stable once its population repeats with at most this period
+const MAX_POPULATION_PERIOD: usize = 64;
+// generations a single object gets to repeat on its own
+const OBJECT_BUDGET: u64 = 1 << 10;
+// generations an unstable soup is followed to tell linear from explosive growth
+const GROWTH_BUDGET: u64 = 1 << 11;
+const MAX_SAMPLES: usize = 10;
+const PROGRESS_INTERVAL: u64 = 10_000;
+
+enum Format {
+    Json,
+    Haul,
+}
+
+struct Options {
+    seed: String,
+    soups: u64,
+    rule: Rule,
+    threads: usize,
+    format: Format,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut options = Options {
+        seed: format!("k_{}", since_epoch.as_secs()),
+        soups: 1000,
+        rule: Rule::LIFE,
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        format: Format::Haul,
+        output: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--seed" => options.seed = value()?,
+            "--soups" => options.soups = value()?.parse().map_err(|_| "invalid soup count")?,
+            "--rule" => {
+                let rule = value()?;
+                options.rule = match parse_rule(&rule) {
+                    Some((rule, None)) if rule.states() == 2 => rule,
+                    _ => return Err(format!("unsupported rule: {}", rule)),
+                };
+            }
+            "--threads" => {
+                options.threads = value()?
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("invalid thread count")?
+            }
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "json" => Format::Json,
+                    "haul" => Format::Haul,
+                    format => return Err(format!("unknown format: {}", format)),
+                }
+            }
+            "--output" => options.output = Some(value()?),
+            "--help" | "-h" => return Err(USAGE.to_owned()),
+            _ => return Err(format!("unknown argument: {}\n{}", arg, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+// FNV-1a, which like ChaCha8 is fixed by its spec,
+// so a soup id gives the same soup on every machine and build
+fn soup_hash(id: &str) -> u64 {
+    id.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn place_soup(u: &mut Universe, id: &str) {
+    let mut seed = [0; 32];
+    seed[..8].copy_from_slice(&soup_hash(id).to_le_bytes());
+    let mut rng = ChaCha8Rng::from_seed(seed);
+    // one bit per cell in row-major order, straight from the stream
+    // since the ways rand turns it into bools may change
+    let mut points = Vec::new();
+    let mut bits = 0;
+    for i in 0..SOUP_SIZE * SOUP_SIZE {
+        if i % 64 == 0 {
+            bits = rng.next_u64();
+        }
+        if (bits >> (i % 64)) & 1 != 0 {
+            points.push((i % SOUP_SIZE, i / SOUP_SIZE, 1));
+        }
+    }
+    u.clear();
+    u.generation = 0;
+    u.set_points(
+        &points,
+        0,
+        0,
+        SOUP_SIZE - 1,
+        SOUP_SIZE - 1,
+        &InsertMode::Copy,
+    );
+}
+
+// runs the soup until its population is periodic
+fn stabilize(u: &mut Universe) -> bool {
+    while u.generation < MAX_GENERATIONS {
+        u.advance_by(STABILIZE_CHUNK);
+        let populations = (0..3 * MAX_POPULATION_PERIOD)
+            .map(|_| {
+                u.step();
+                u.population()
+            })
+            .collect::<Vec<_>>();
+        let repeats =
+            |p: usize| (p..populations.len()).all(|t| populations[t] == populations[t - p]);
+        if (1..=MAX_POPULATION_PERIOD).any(repeats) {
+            return true;
+        }
+    }
+    false
+}
+
+// apgcode of an object, or None if it dies on its own
+fn object_name(scratch: &mut Universe, object: &[(i64, i64, u8)]) -> Option<String> {
+    let (x1, y1, x2, y2) = object.iter().fold(
+        (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+        |(x1, y1, x2, y2), &(x, y, _)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+    );
+    scratch.clear();
+    scratch.set_points(object, x1, y1, x2, y2, &InsertMode::Or);
+    let Some(p) = scratch.find_period(OBJECT_BUDGET) else {
+        return Some("zz_UNKNOWN".to_owned());
+    };
+    // objects that only settle on their own were still interacting with something
+    if p.start != scratch.generation {
+        return Some("zz_UNSEPARATED".to_owned());
+    }
+    scratch.apgcode_for(p)
+}
+
+#[derive(Default, Serialize)]
+struct Census {
+    counts: BTreeMap<String, u64>,
+    samples: BTreeMap<String, Vec<u64>>,
+}
+
+impl Census {
+    fn add(&mut self, object: String, soup: u64) {
+        let samples = self.samples.entry(object.clone()).or_default();
+        if samples.len() < MAX_SAMPLES && samples.last() != Some(&soup) {
+            samples.push(soup);
+        }
+        *self.counts.entry(object).or_default() += 1;
+    }
+    fn merge(&mut self, other: Census) {
+        for (object, count) in other.counts {
+            *self.counts.entry(object).or_default() += count;
+        }
+        for (object, samples) in other.samples {
+            let merged = self.samples.entry(object).or_default();
+            merged.extend(samples);
+            merged.sort_unstable();
+            merged.truncate(MAX_SAMPLES);
+        }
+    }
+}
+
+fn search(options: &Options, worker: usize, searched: &AtomicU64) -> Census {
+    let mut census = Census::default();
+    let mut u = Universe::default();
+    u.rule = options.rule;
+    let mut scratch = Universe::default();
+    scratch.rule = options.rule;
+    // objects seen before, by the cells they were first seen with
+    let mut names = FxHashMap::<String, Option<String>>::default();
+
+    for soup in (worker as u64..options.soups).step_by(options.threads) {
+        place_soup(&mut u, &format!("{}{}", options.seed, soup));
+        if stabilize(&mut u) {
+            for object in u.separated_objects(OBJECT_BUDGET) {
+                let seen = apgcode::encode(object.iter().map(|&(x, y, _)| (x, y)));
+                let name = names
+                    .entry(seen)
+                    .or_insert_with(|| object_name(&mut scratch, &object));
+                if let Some(name) = name {
+                    census.add(name.clone(), soup);
+                }
+            }
+        } else {
+            let name = match u.classify(GROWTH_BUDGET).class {
+                PatternClass::Gun { .. } | PatternClass::Puffer { .. } => "zz_LINEAR",
+                PatternClass::UnboundedGrowth => "zz_EXPLOSIVE",
+                _ => "zz_UNKNOWN",
+            };
+            census.add(name.to_owned(), soup);
+        }
+
+        let done = searched.fetch_add(1, Ordering::Relaxed) + 1;
+        if done % PROGRESS_INTERVAL == 0 {
+            eprintln!("{} soups searched", done);
+        }
+    }
+    census
+}
+
+// catagolue writes rules in lowercase without the slash, e.g. b3s23
+fn rule_slug(rule: &Rule) -> String {
+    rule.to_string().to_lowercase().replace('/', "")
+}
+
+fn haul(options: &Options, census: &Census) -> String {
+    let mut counts = census.counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+    let mut haul = format!(
+        "@VERSION gol-{}\n@ROOT {}\n@RULE {}\n@SYMMETRY C1\n@NUM_SOUPS {}\n@NUM_OBJECTS {}\n\n",
+        env!("CARGO_PKG_VERSION"),
+        options.seed,
+        rule_slug(&options.rule),
+        options.soups,
+        census.counts.values().sum::<u64>(),
+    );
+    haul.push_str("@CENSUS TABLE\n");
+    for (object, count) in &counts {
+        haul.push_str(&format!("{} {}\n", object, count));
+    }
+    haul.push_str("\n@SAMPLE_SOUPIDS\n");
+    for (object, _) in &counts {
+        let samples = census.samples[*object]
+            .iter()
+            .map(|soup| soup.to_string())
+            .collect::<Vec<_>>();
+        haul.push_str(&format!("{} {}\n", object, samples.join(" ")));
+    }
+    haul
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    root: &'a str,
+    rule: String,
+    soups: u64,
+    objects: u64,
+    census: &'a BTreeMap<String, u64>,
+    samples: &'a BTreeMap<String, Vec<u64>>,
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    let start = Instant::now();
+    let searched = AtomicU64::new(0);
+    let mut census = Census::default();
+    thread::scope(|s| {
+        let (options, searched) = (&options, &searched);
+        let workers = (0..options.threads)
+            .map(|worker| s.spawn(move || search(options, worker, searched)))
+            .collect::<Vec<_>>();
+        for worker in workers {
+            census.merge(worker.join().unwrap());
+        }
+    });
+    eprintln!(
+        "searched {} soups in {:.1}s",
+        options.soups,
+        start.elapsed().as_secs_f64()
+    );
+
+    let output = match options.format {
+        Format::Haul => haul(&options, &census),
+        Format::Json => serde_json::to_string_pretty(&Report {
+            root: &options.seed,
+            rule: rule_slug(&options.rule),
+            soups: options.soups,
+            objects: census.counts.values().sum(),
+            census: &census.counts,
+            samples: &census.samples,
+        })
+        .unwrap(),
+    };
+    match &options.output {
+        Some(path) => fs::write(path, output).unwrap(),
+        None => print!("{}", output),
+    }
+}
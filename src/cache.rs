@@ -0,0 +1,91 @@
+use rustc_hash::FxHashMap;
+use std::cmp::Eq;
+use std::hash::Hash;
+
+// fixed-size map with CLOCK eviction:
+// a hit marks its entry, and the hand sweeps past marked entries once before evicting them
+pub struct Cache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    slots: Vec<(K, V, bool)>,
+    index: FxHashMap<K, usize>,
+    hand: usize,
+    capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            slots: Vec::new(),
+            index: FxHashMap::default(),
+            hand: 0,
+            capacity,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        match self.index.get(key) {
+            Some(&i) => {
+                self.hits += 1;
+                let (_, value, referenced) = &mut self.slots[i];
+                *referenced = true;
+                Some(value)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(&i) = self.index.get(&key) {
+            self.slots[i] = (key, value, true);
+            return;
+        }
+        if self.slots.len() < self.capacity {
+            self.index.insert(key.clone(), self.slots.len());
+            self.slots.push((key, value, false));
+            return;
+        }
+        while std::mem::replace(&mut self.slots[self.hand].2, false) {
+            self.hand = (self.hand + 1) % self.capacity;
+        }
+        let (old_key, _, _) = &self.slots[self.hand];
+        self.index.remove(old_key);
+        self.index.insert(key.clone(), self.hand);
+        self.slots[self.hand] = (key, value, false);
+        self.hand = (self.hand + 1) % self.capacity;
+        self.evictions += 1;
+    }
+    // rewrites every entry, dropping those mapped to None
+    pub fn retain_map(&mut self, mut f: impl FnMut(K, V) -> Option<(K, V)>) {
+        let slots = std::mem::take(&mut self.slots);
+        self.index.clear();
+        self.hand = 0;
+        for (key, value, referenced) in slots {
+            if let Some((key, value)) = f(key, value) {
+                self.index.insert(key.clone(), self.slots.len());
+                self.slots.push((key, value, referenced));
+            }
+        }
+    }
+}
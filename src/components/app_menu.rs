@@ -1,19 +1,29 @@
 use crate::{
     app::{GolContext, use_fit_universe},
     components::{
-        Button, ButtonVariant, Dialog, FileInput, Icon, IconSize, Link, LinkVariant, Popover,
-        PopoverPlacement, PopoverTrigger, Surface, TextArea, use_toast,
+        Button, ButtonVariant, Dialog, FileInput, Icon, IconSize, Input, Link, LinkVariant,
+        Popover, PopoverPlacement, PopoverTrigger, Surface, TextArea, use_toast,
     },
-    parse::rle,
-    universe::InsertMode,
-    utils::{base64_gz_from_str, download_text_file, str_from_base64_gz},
+    draw::{self, Colors},
+    parse::{
+        self, Format, life, macrocell, plaintext,
+        rle::{self, ParseMode},
+    },
+    record::{RecordArea, RecordOptions, Recorder},
+    session::Session,
+    svg::{self, SvgOptions},
+    universe::{MAX_SLOW_ADVANCE, MAX_STEP},
+    utils::{base64_gz_from_str, download_file, download_text_file},
 };
-use leptos::{logging, prelude::*, task::spawn_local};
+use leptos::{prelude::*, task::spawn_local};
 use leptos_router::hooks::use_url;
 use leptos_use::{UseClipboardReturn, use_clipboard};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::File;
 
+// warnings listed under the import text before the rest are summarized
+const MAX_WARNINGS: usize = 3;
+
 #[component]
 pub fn ImportForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
     let GolContext { universe, .. } = use_context::<GolContext>().unwrap();
@@ -33,24 +43,41 @@ pub fn ImportForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
         });
     };
     let (error_text, set_error_text) = signal("".to_owned());
+    let (is_strict, set_is_strict) = signal(false);
+    let mode = move || {
+        if is_strict.get() {
+            ParseMode::Strict
+        } else {
+            ParseMode::Lenient
+        }
+    };
+    // diagnostics for the text as it is, before importing
+    let diagnostics = Memo::new(move |_| {
+        let text = rle.get();
+        if text.trim().is_empty() || Format::detect(&text) == Format::Macrocell {
+            return (None, Vec::new());
+        }
+        match parse::parse(&text, mode()) {
+            Ok(parsed) => (None, parsed.warnings),
+            Err(e) => (Some(e), Vec::new()),
+        }
+    });
 
     view! {
         <form on:submit=move |ev| {
             ev.prevent_default();
-            if let Ok(points) = rle::iter_alive(&rle.get()).map(|it| it.collect::<Vec<_>>()) {
-                universe
-                    .update(|u| {
-                        let half = 1i64 << (u.level() - 1);
-                        u.set_points(&points, -half, -half, half - 1, half - 1, &InsertMode::Copy);
-                    });
-                use_fit_universe();
-                close.run(());
-            } else {
-                set_error_text.set("Invalid format".to_owned());
+            let result = universe
+                .try_update(|u| parse::load_universe(&rle.get(), mode(), u))
+                .unwrap();
+            if let Err(e) = result {
+                set_error_text.set(e);
+                return;
             }
+            use_fit_universe();
+            close.run(());
         }>
             <div class="flex flex-col gap-2">
-                <p>Supports RLE file format.</p>
+                <p>Supports RLE, macrocell, plaintext and Life 1.05/1.06 files, or an apgcode.</p>
                 <div class="flex flex-col gap-2">
                     <TextArea
                         class="w-full text-sm resize-none"
@@ -59,8 +86,65 @@ pub fn ImportForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
                         attr:cols=70
                         attr:rows=8
                         prop:value=move || rle.get()
+                        on:input=move |e| {
+                            set_rle.set(event_target_value(&e));
+                            set_error_text.set("".to_owned());
+                        }
                     />
-                    <FileInput on_change=on_file_change accept=".rle,.txt" />
+                    <FileInput on_change=on_file_change accept=".rle,.mc,.cells,.lif,.life,.txt" />
+                    <label class="flex items-center gap-2 text-sm">
+                        <input
+                            type="checkbox"
+                            class="accent-white"
+                            prop:checked=move || is_strict.get()
+                            on:change=move |e| set_is_strict.set(event_target_checked(&e))
+                        />
+                        "Strict (reject anything malformed instead of warning)"
+                    </label>
+                    {move || {
+                        let (error, warnings) = diagnostics.get();
+                        let hidden = warnings.len().saturating_sub(MAX_WARNINGS);
+                        view! {
+                            <div class="flex flex-col gap-1 text-sm">
+                                {error
+                                    .filter(|_| error_text.get().is_empty())
+                                    .map(|e| {
+                                        view! {
+                                            <div class="text-red-400 flex items-center gap-2">
+                                                <Icon
+                                                    icon=icondata::LuCircleAlert
+                                                    size=IconSize::Small
+                                                />
+                                                <span>{e.to_string()}</span>
+                                            </div>
+                                        }
+                                    })}
+                                {warnings
+                                    .into_iter()
+                                    .take(MAX_WARNINGS)
+                                    .map(|w| {
+                                        view! {
+                                            <div class="text-amber-400 flex items-center gap-2">
+                                                <Icon
+                                                    icon=icondata::LuTriangleAlert
+                                                    size=IconSize::Small
+                                                />
+                                                <span>{w.to_string()}</span>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()}
+                                {(hidden > 0)
+                                    .then(|| {
+                                        view! {
+                                            <span class="text-amber-400">
+                                                {format!("and {} more", hidden)}
+                                            </span>
+                                        }
+                                    })}
+                            </div>
+                        }
+                    }}
                     {move || {
                         if !error_text.get().is_empty() {
                             view! {
@@ -88,6 +172,516 @@ pub fn ImportForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
     }
 }
 
+#[component]
+pub fn GoToForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
+    let GolContext { universe, .. } = ctx;
+    let (generation, set_generation) = signal(String::new());
+    let (error_text, set_error_text) = signal("".to_owned());
+
+    view! {
+        <form on:submit=move |ev| {
+            ev.prevent_default();
+            let Ok(target) = generation.get().trim().replace([',', '_'], "").parse::<u64>() else {
+                set_error_text.set("Invalid generation".to_owned());
+                return;
+            };
+            let current = universe.with(|u| u.generation);
+            if target < current {
+                set_error_text.set(format!("Already at generation {}", current));
+                return;
+            }
+            // without hashlife every generation is stepped on its own
+            if universe.with(|u| u.max_step() < MAX_STEP) && target - current > MAX_SLOW_ADVANCE {
+                let message = format!("This rule goes at most {} generations at a time", MAX_SLOW_ADVANCE);
+                set_error_text.set(message);
+                return;
+            }
+            ctx.begin_run();
+            universe
+                .update(|u| {
+                    u.advance_to(target);
+                });
+            set_error_text.set("".to_owned());
+            close.run(());
+        }>
+            <div class="flex flex-col gap-2">
+                <Input
+                    class="w-full py-1"
+                    attr:inputmode="numeric"
+                    attr:placeholder="Generation"
+                    on:input=move |e| set_generation.set(event_target_value(&e))
+                    prop:value=move || generation.get()
+                />
+                {move || {
+                    if !error_text.get().is_empty() {
+                        view! {
+                            <div class="text-red-400 flex items-center gap-2">
+                                // looks better with this pixel
+                                <div class="mb-px">
+                                    <Icon icon=icondata::LuCircleAlert size=IconSize::Small />
+                                </div>
+                                <span class="text-sm">{move || error_text.get()}</span>
+                            </div>
+                        }
+                            .into_any()
+                    } else {
+                        ().into_any()
+                    }
+                }}
+                <div class="w-full flex justify-end">
+                    <Button variant=ButtonVariant::Primary attr:r#type="submit" class="rounded-md">
+                        GO
+                    </Button>
+                </div>
+            </div>
+        </form>
+    }
+}
+
+// #rrggbb from a color input as 0xRRGGBBAA
+fn parse_color(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    Some((u32::from_str_radix(hex, 16).ok()? << 8) | 0xFF)
+}
+fn color_hex(color: u32) -> String {
+    format!("#{:06x}", color >> 8)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportTarget {
+    View,
+    Selection,
+    Pattern,
+}
+
+// cells the target covers, the view's being whatever is at least partly visible
+fn target_rect(ctx: &GolContext, target: ExportTarget) -> Result<(i64, i64, i64, i64), String> {
+    let GolContext {
+        universe,
+        canvas_size,
+        viewport,
+        selection_rect,
+        ..
+    } = *ctx;
+    match target {
+        ExportTarget::View => {
+            let (width, height) = canvas_size.get();
+            let (x1, y1) = viewport.with(|vp| vp.to_world_coords(0, 0));
+            let (x2, y2) = viewport.with(|vp| vp.to_world_coords(width as i32, height as i32));
+            Ok((
+                x1.floor() as i64,
+                y1.floor() as i64,
+                x2.ceil() as i64 - 1,
+                y2.ceil() as i64 - 1,
+            ))
+        }
+        ExportTarget::Selection => selection_rect.get().ok_or("Nothing is selected".to_owned()),
+        ExportTarget::Pattern => {
+            if universe.with(|u| u.population()) == 0 {
+                return Err("The pattern is empty".to_owned());
+            }
+            Ok(universe.with(|u| u.get_bounding_rect()))
+        }
+    }
+}
+
+#[component]
+fn TargetSelect(
+    target: ReadSignal<ExportTarget>,
+    set_target: WriteSignal<ExportTarget>,
+) -> impl IntoView {
+    let GolContext { selection_rect, .. } = use_context::<GolContext>().unwrap();
+    view! {
+        <Field label="Area">
+            <select
+                class="px-2 py-1 rounded-md bg-neutral-900"
+                on:change=move |e| {
+                    set_target
+                        .set(
+                            match event_target_value(&e).as_str() {
+                                "selection" => ExportTarget::Selection,
+                                "pattern" => ExportTarget::Pattern,
+                                _ => ExportTarget::View,
+                            },
+                        )
+                }
+            >
+                <option value="view" selected=move || target.get() == ExportTarget::View>
+                    View
+                </option>
+                <option
+                    value="selection"
+                    selected=move || target.get() == ExportTarget::Selection
+                    disabled=move || selection_rect.get().is_none()
+                >
+                    Selection
+                </option>
+                <option value="pattern" selected=move || target.get() == ExportTarget::Pattern>
+                    Whole pattern
+                </option>
+            </select>
+        </Field>
+    }
+}
+
+#[component]
+fn Field(#[prop(into)] label: String, children: Children) -> impl IntoView {
+    view! {
+        <label class="flex justify-between items-center gap-8 text-sm">
+            <span class="text-neutral-400">{label}</span>
+            {children()}
+        </label>
+    }
+}
+
+#[component]
+pub fn RecordForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
+    let GolContext {
+        universe,
+        canvas_size,
+        viewport,
+        selection_rect,
+        recorder,
+        is_ticking,
+        ..
+    } = ctx;
+    let defaults = RecordOptions::default();
+    let (target, set_target) = signal(if selection_rect.get_untracked().is_some() {
+        ExportTarget::Selection
+    } else {
+        ExportTarget::View
+    });
+    let (frames, set_frames) = signal(defaults.frames.to_string());
+    let (generations, set_generations) = signal(defaults.generations_per_frame.to_string());
+    let (cell_size, set_cell_size) = signal(defaults.cell_size.to_string());
+    // milliseconds here, gifs count hundredths of a second
+    let (delay, set_delay) = signal((defaults.delay * 10).to_string());
+    let (alive, set_alive) = signal(color_hex(defaults.colors.alive));
+    let (background, set_background) = signal(color_hex(defaults.colors.background));
+    let (error_text, set_error_text) = signal("".to_owned());
+
+    let options = move || -> Result<RecordOptions, String> {
+        let area = match target.get() {
+            ExportTarget::View => {
+                let (width, height) = canvas_size.get();
+                RecordArea::Viewport { width, height }
+            }
+            target => {
+                let (x1, y1, x2, y2) = target_rect(&ctx, target)?;
+                RecordArea::Rect(x1, y1, x2, y2)
+            }
+        };
+        Ok(RecordOptions {
+            area,
+            frames: frames
+                .get()
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or("Invalid frame count")?,
+            generations_per_frame: generations
+                .get()
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or("Invalid generations per frame")?,
+            cell_size: cell_size
+                .get()
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&size: &f64| size > 0.0)
+                .ok_or("Invalid cell size")?,
+            colors: Colors {
+                alive: parse_color(&alive.get()).ok_or("Invalid color")?,
+                background: parse_color(&background.get()).ok_or("Invalid color")?,
+            },
+            delay: delay
+                .get()
+                .trim()
+                .parse::<u16>()
+                .map(|ms| ms / 10)
+                .map_err(|_| "Invalid frame delay")?,
+        })
+    };
+
+    view! {
+        <form on:submit=move |ev| {
+            ev.prevent_default();
+            let options = match options() {
+                Ok(options) => options,
+                Err(e) => {
+                    set_error_text.set(e);
+                    return;
+                }
+            };
+            let mut r = Recorder::new(options);
+            universe.with(|u| viewport.with(|vp| r.capture(u, vp)));
+            recorder.set(Some(r));
+            if options.frames == 1 {
+                ctx.finish_recording();
+            } else {
+                is_ticking.set(true);
+            }
+            set_error_text.set("".to_owned());
+            close.run(());
+        }>
+            <div class="flex flex-col gap-2 min-w-64">
+                <TargetSelect target=target set_target=set_target />
+                <Field label="Frames">
+                    <Input
+                        class="w-24 py-1"
+                        attr:inputmode="numeric"
+                        on:input=move |e| set_frames.set(event_target_value(&e))
+                        prop:value=move || frames.get()
+                    />
+                </Field>
+                <Field label="Generations per frame">
+                    <Input
+                        class="w-24 py-1"
+                        attr:inputmode="numeric"
+                        on:input=move |e| set_generations.set(event_target_value(&e))
+                        prop:value=move || generations.get()
+                    />
+                </Field>
+                <Field label="Frame delay (ms)">
+                    <Input
+                        class="w-24 py-1"
+                        attr:inputmode="numeric"
+                        on:input=move |e| set_delay.set(event_target_value(&e))
+                        prop:value=move || delay.get()
+                    />
+                </Field>
+                <Show when=move || target.get() != ExportTarget::View>
+                    <Field label="Cell size (px)">
+                        <Input
+                            class="w-24 py-1"
+                            attr:inputmode="decimal"
+                            on:input=move |e| set_cell_size.set(event_target_value(&e))
+                            prop:value=move || cell_size.get()
+                        />
+                    </Field>
+                </Show>
+                <Field label="Cell color">
+                    <input
+                        type="color"
+                        class="bg-transparent"
+                        on:input=move |e| set_alive.set(event_target_value(&e))
+                        prop:value=move || alive.get()
+                    />
+                </Field>
+                <Field label="Background">
+                    <input
+                        type="color"
+                        class="bg-transparent"
+                        on:input=move |e| set_background.set(event_target_value(&e))
+                        prop:value=move || background.get()
+                    />
+                </Field>
+                {move || {
+                    if !error_text.get().is_empty() {
+                        view! {
+                            <div class="text-red-400 flex items-center gap-2">
+                                // looks better with this pixel
+                                <div class="mb-px">
+                                    <Icon icon=icondata::LuCircleAlert size=IconSize::Small />
+                                </div>
+                                <span class="text-sm">{move || error_text.get()}</span>
+                            </div>
+                        }
+                            .into_any()
+                    } else {
+                        ().into_any()
+                    }
+                }}
+                <div class="w-full flex justify-end">
+                    <Button variant=ButtonVariant::Primary attr:r#type="submit" class="rounded-md">
+                        RECORD
+                    </Button>
+                </div>
+            </div>
+        </form>
+    }
+}
+
+#[component]
+pub fn SvgForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
+    let GolContext {
+        universe,
+        name,
+        selection_rect,
+        ..
+    } = ctx;
+    let defaults = SvgOptions::default();
+    let (target, set_target) = signal(if selection_rect.get_untracked().is_some() {
+        ExportTarget::Selection
+    } else {
+        ExportTarget::Pattern
+    });
+    let (cell_size, set_cell_size) = signal(defaults.cell_size.to_string());
+    let (alive, set_alive) = signal(color_hex(defaults.colors.alive));
+    let (background, set_background) = signal(color_hex(defaults.colors.background));
+    let (grid_lines, set_grid_lines) = signal(defaults.grid_lines);
+    let (bounding_box, set_bounding_box) = signal(defaults.bounding_box);
+    let (error_text, set_error_text) = signal("".to_owned());
+
+    let options = move || -> Result<SvgOptions, String> {
+        Ok(SvgOptions {
+            cell_size: cell_size
+                .get()
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&size: &f64| size > 0.0)
+                .ok_or("Invalid cell size")?,
+            colors: Colors {
+                alive: parse_color(&alive.get()).ok_or("Invalid color")?,
+                background: parse_color(&background.get()).ok_or("Invalid color")?,
+            },
+            grid_lines: grid_lines.get(),
+            bounding_box: bounding_box.get(),
+        })
+    };
+
+    view! {
+        <form on:submit=move |ev| {
+            ev.prevent_default();
+            let result = options()
+                .and_then(|options| Ok((options, target_rect(&ctx, target.get())?)));
+            let (options, (x1, y1, x2, y2)) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    set_error_text.set(e);
+                    return;
+                }
+            };
+            let svg = universe.with(|u| svg::render(u, x1, y1, x2, y2, &options));
+            download_text_file(&format!("{}.svg", name.get()), &svg);
+            set_error_text.set("".to_owned());
+            close.run(());
+        }>
+            <div class="flex flex-col gap-2 min-w-64">
+                <TargetSelect target=target set_target=set_target />
+                <Field label="Cell size (px)">
+                    <Input
+                        class="w-24 py-1"
+                        attr:inputmode="decimal"
+                        on:input=move |e| set_cell_size.set(event_target_value(&e))
+                        prop:value=move || cell_size.get()
+                    />
+                </Field>
+                <Field label="Cell color">
+                    <input
+                        type="color"
+                        class="bg-transparent"
+                        on:input=move |e| set_alive.set(event_target_value(&e))
+                        prop:value=move || alive.get()
+                    />
+                </Field>
+                <Field label="Background">
+                    <input
+                        type="color"
+                        class="bg-transparent"
+                        on:input=move |e| set_background.set(event_target_value(&e))
+                        prop:value=move || background.get()
+                    />
+                </Field>
+                <Field label="Grid lines">
+                    <input
+                        type="checkbox"
+                        class="accent-white"
+                        prop:checked=move || grid_lines.get()
+                        on:change=move |e| set_grid_lines.set(event_target_checked(&e))
+                    />
+                </Field>
+                <Field label="Bounding box">
+                    <input
+                        type="checkbox"
+                        class="accent-white"
+                        prop:checked=move || bounding_box.get()
+                        on:change=move |e| set_bounding_box.set(event_target_checked(&e))
+                    />
+                </Field>
+                {move || {
+                    if !error_text.get().is_empty() {
+                        view! {
+                            <div class="text-red-400 flex items-center gap-2">
+                                // looks better with this pixel
+                                <div class="mb-px">
+                                    <Icon icon=icondata::LuCircleAlert size=IconSize::Small />
+                                </div>
+                                <span class="text-sm">{move || error_text.get()}</span>
+                            </div>
+                        }
+                            .into_any()
+                    } else {
+                        ().into_any()
+                    }
+                }}
+                <div class="w-full flex justify-end">
+                    <Button variant=ButtonVariant::Primary attr:r#type="submit" class="rounded-md">
+                        EXPORT
+                    </Button>
+                </div>
+            </div>
+        </form>
+    }
+}
+
+#[component]
+pub fn OpenSessionForm(#[prop(into)] close: Callback<()>) -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
+    let logging = use_toast();
+    let (error_text, set_error_text) = signal("".to_owned());
+    let on_file_change = move |file: File| {
+        spawn_local(async move {
+            let json = match JsFuture::from(file.text()).await {
+                Ok(text) => text.as_string().unwrap_or_default(),
+                Err(err) => {
+                    logging.error(&err.as_string().unwrap_or_default());
+                    return;
+                }
+            };
+            match Session::from_json(&json).and_then(|session| ctx.restore_session(&session)) {
+                Ok(()) => {
+                    set_error_text.set("".to_owned());
+                    logging.log("Session restored!");
+                    close.run(());
+                }
+                Err(e) => set_error_text.set(e.to_string()),
+            }
+        });
+    };
+
+    view! {
+        <div class="flex flex-col gap-2">
+            <p>Replaces the pattern, undo history, view and speed with a saved session.</p>
+            <FileInput on_change=on_file_change accept=".json" />
+            {move || {
+                if !error_text.get().is_empty() {
+                    view! {
+                        <div class="text-red-400 flex items-center gap-2">
+                            // looks better with this pixel
+                            <div class="mb-px">
+                                <Icon icon=icondata::LuCircleAlert size=IconSize::Small />
+                            </div>
+                            <span class="text-sm">{move || error_text.get()}</span>
+                        </div>
+                    }
+                        .into_any()
+                } else {
+                    ().into_any()
+                }
+            }}
+        </div>
+    }
+}
+
 #[component]
 pub fn MenuButton(
     children: Children,
@@ -108,7 +702,19 @@ pub fn MenuButton(
 pub fn AppMenu() -> impl IntoView {
     let (is_open, set_is_open) = signal(false);
     let (is_import_open, set_is_import_open) = signal(false);
-    let GolContext { universe, name, .. } = use_context::<GolContext>().unwrap();
+    let (is_goto_open, set_is_goto_open) = signal(false);
+    let (is_record_open, set_is_record_open) = signal(false);
+    let (is_svg_open, set_is_svg_open) = signal(false);
+    let (is_session_open, set_is_session_open) = signal(false);
+    let ctx = use_context::<GolContext>().unwrap();
+    let GolContext {
+        universe,
+        name,
+        canvas_size,
+        viewport,
+        recorder,
+        ..
+    } = ctx;
 
     let url = use_url();
     let UseClipboardReturn { copy, .. } = use_clipboard();
@@ -134,7 +740,15 @@ pub fn AppMenu() -> impl IntoView {
                             universe
                                 .with(|u| {
                                     let (x1, y1, x2, y2) = u.get_bounding_rect();
-                                    let rle = rle::from_iter(u.iter_alive(), x1, y1, x2, y2);
+                                    let rle = rle::from_iter(
+                                        u.iter_alive(),
+                                        x1,
+                                        y1,
+                                        x2,
+                                        y2,
+                                        &u.rule,
+                                        u.grid.as_ref(),
+                                    );
                                     let mut filename = name.get();
                                     if !filename.ends_with(".rle") {
                                         filename = format!("{}.rle", filename);
@@ -143,8 +757,100 @@ pub fn AppMenu() -> impl IntoView {
                                 });
                         }>
                             <Icon icon=icondata::LuFileDown />
-                            Export
+                            Export RLE
+                        </MenuButton>
+                        <MenuButton on_press=move || {
+                            universe
+                                .with(|u| {
+                                    let mc = macrocell::write(u);
+                                    let mut filename = name.get();
+                                    if !filename.ends_with(".mc") {
+                                        filename = format!("{}.mc", filename);
+                                    }
+                                    download_text_file(&filename, &mc);
+                                });
+                        }>
+                            <Icon icon=icondata::LuFileDown />
+                            Export macrocell
+                        </MenuButton>
+                        <MenuButton on_press=move || {
+                            universe
+                                .with(|u| {
+                                    let (x1, y1, _, _) = u.get_bounding_rect();
+                                    let cells = plaintext::write(u.iter_alive(), x1, y1, &name.get());
+                                    let mut filename = name.get();
+                                    if !filename.ends_with(".cells") {
+                                        filename = format!("{}.cells", filename);
+                                    }
+                                    download_text_file(&filename, &cells);
+                                });
+                        }>
+                            <Icon icon=icondata::LuFileDown />
+                            Export plaintext
+                        </MenuButton>
+                        <MenuButton on_press=move || {
+                            universe
+                                .with(|u| {
+                                    let lif = life::write_106(u.iter_alive());
+                                    let mut filename = name.get();
+                                    if !filename.ends_with(".lif") {
+                                        filename = format!("{}.lif", filename);
+                                    }
+                                    download_text_file(&filename, &lif);
+                                });
+                        }>
+                            <Icon icon=icondata::LuFileDown />
+                            Export Life 1.06
                         </MenuButton>
+                        <MenuButton on_press=move || {
+                            universe
+                                .with(|u| {
+                                    let (x1, y1, x2, y2) = u.get_bounding_rect();
+                                    let cell_size = draw::fit_cell_size(
+                                        x2 - x1 + 1,
+                                        y2 - y1 + 1,
+                                        draw::IMAGE_CELL_SIZE,
+                                        draw::MAX_IMAGE_SIZE,
+                                    );
+                                    let png = draw::render_rect(u, x1, y1, x2, y2, cell_size, &Colors::default())
+                                        .to_png();
+                                    download_file(&format!("{}.png", name.get()), &png);
+                                });
+                        }>
+                            <Icon icon=icondata::LuImageDown />
+                            Export image
+                        </MenuButton>
+                        <MenuButton on_press=move || {
+                            let (width, height) = canvas_size.get();
+                            universe
+                                .with(|u| {
+                                    let png = draw::render_viewport(
+                                        u,
+                                        &viewport.get(),
+                                        width,
+                                        height,
+                                        &Colors::default(),
+                                    )
+                                    .to_png();
+                                    download_file(&format!("{} (view).png", name.get()), &png);
+                                });
+                        }>
+                            <Icon icon=icondata::LuImageDown />
+                            Export view
+                        </MenuButton>
+                        <PopoverTrigger is_open=is_svg_open set_is_open=set_is_svg_open>
+                            <MenuButton on_press=move || {
+                                set_is_svg_open.set(true);
+                            }>
+                                <Icon icon=icondata::LuFileImage />
+                                "Export SVG…"
+                            </MenuButton>
+                            <Dialog>
+                                <h2 class="text-lg font-bold text-center">EXPORT SVG</h2>
+                                <div class="border-t border-neutral-800 w-full" />
+                                <SvgForm close=move || set_is_svg_open.set(false) />
+                            </Dialog>
+                        </PopoverTrigger>
                     </div>
 
                     <PopoverTrigger is_open=is_import_open set_is_open=set_is_import_open>
@@ -161,11 +867,79 @@ pub fn AppMenu() -> impl IntoView {
                         </Dialog>
                     </PopoverTrigger>
 
+                    <MenuButton on_press=move || {
+                        let json = ctx.session().to_json();
+                        download_text_file(&format!("{}.session.json", name.get()), &json);
+                    }>
+                        <Icon icon=icondata::LuSave />
+                        "Save session"
+                    </MenuButton>
+                    <PopoverTrigger is_open=is_session_open set_is_open=set_is_session_open>
+                        <MenuButton on_press=move || {
+                            set_is_session_open.set(true);
+                        }>
+                            <Icon icon=icondata::LuFolderOpen />
+                            "Open session…"
+                        </MenuButton>
+                        <Dialog>
+                            <h2 class="text-lg font-bold text-center">OPEN SESSION</h2>
+                            <div class="border-t border-neutral-800 w-full" />
+                            <OpenSessionForm close=move || set_is_session_open.set(false) />
+                        </Dialog>
+                    </PopoverTrigger>
+
+                    <PopoverTrigger is_open=is_goto_open set_is_open=set_is_goto_open>
+                        <MenuButton on_press=move || {
+                            set_is_goto_open.set(true);
+                        }>
+                            <Icon icon=icondata::LuSkipForward />
+                            "Go to generation…"
+                        </MenuButton>
+                        <Dialog>
+                            <h2 class="text-lg font-bold text-center">GO TO GENERATION</h2>
+                            <div class="border-t border-neutral-800 w-full" />
+                            <GoToForm close=move || set_is_goto_open.set(false) />
+                        </Dialog>
+                    </PopoverTrigger>
+                    {move || {
+                        if recorder.with(Option::is_some) {
+                            view! {
+                                <MenuButton on_press=move || {
+                                    ctx.finish_recording();
+                                }>
+                                    <Icon icon=icondata::LuCircleStop />
+                                    "Stop recording"
+                                </MenuButton>
+                            }
+                                .into_any()
+                        } else {
+                            view! {
+                                <PopoverTrigger
+                                    is_open=is_record_open
+                                    set_is_open=set_is_record_open
+                                >
+                                    <MenuButton on_press=move || {
+                                        set_is_record_open.set(true);
+                                    }>
+                                        <Icon icon=icondata::LuVideo />
+                                        "Record GIF…"
+                                    </MenuButton>
+                                    <Dialog>
+                                        <h2 class="text-lg font-bold text-center">RECORD GIF</h2>
+                                        <div class="border-t border-neutral-800 w-full" />
+                                        <RecordForm close=move || set_is_record_open.set(false) />
+                                    </Dialog>
+                                </PopoverTrigger>
+                            }
+                                .into_any()
+                        }
+                    }}
+
                     <MenuButton on_press=move || {
                         let rle = universe
                             .with(|u| {
                                 let (x1, y1, x2, y2) = u.get_bounding_rect();
-                                rle::from_iter(u.iter_alive(), x1, y1, x2, y2)
+                                rle::from_iter(u.iter_alive(), x1, y1, x2, y2, &u.rule, u.grid.as_ref())
                             });
                         spawn_local(async move {
                             let base64 = base64_gz_from_str(&rle).await.unwrap();
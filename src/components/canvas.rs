@@ -4,7 +4,10 @@ use leptos::prelude::*;
 use leptos::tachys::html::node_ref::NodeRefContainer;
 use leptos_use::use_raf_fn;
 use leptos_use::{UseRafFnCallbackArgs, use_debounce_fn_with_arg, use_resize_observer};
-use web_sys::{CanvasRenderingContext2d, js_sys, wasm_bindgen::JsCast};
+use web_sys::{
+    CanvasRenderingContext2d, ImageData, js_sys,
+    wasm_bindgen::{Clamped, JsCast},
+};
 
 pub fn create_2d_context(
     canvas: web_sys::HtmlCanvasElement,
@@ -18,6 +21,30 @@ pub fn create_2d_context(
         .unwrap()
 }
 
+// puts a canvas' pixels on screen
+pub struct Presenter {
+    ctx: CanvasRenderingContext2d,
+}
+impl Presenter {
+    pub fn new(ctx: CanvasRenderingContext2d) -> Self {
+        Self { ctx }
+    }
+    pub fn present(&self, canvas: &mut Canvas) {
+        if !canvas.is_dirty {
+            return;
+        }
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&canvas.buffer),
+            canvas.width,
+            canvas.height,
+        );
+        if let Ok(data) = image_data {
+            self.ctx.put_image_data(&data, 0.0, 0.0).unwrap();
+        }
+        canvas.is_dirty = false;
+    }
+}
+
 #[derive(Clone)]
 pub struct StageContext {
     pub canvas_size: ReadSignal<(u32, u32), LocalStorage>,
@@ -57,7 +84,7 @@ pub fn Layer(
 ) -> impl IntoView {
     let StageContext { canvas_size } = use_context::<StageContext>().unwrap();
     let canvas_ref = NodeRef::<html::Canvas>::new();
-    let (_canvas, set_canvas) = signal_local::<Option<Canvas>>(None);
+    let (_canvas, set_canvas) = signal_local::<Option<(Canvas, Presenter)>>(None);
     let (is_ready, set_is_ready) = signal_local(false);
     canvas_ref.on_load(move |canvas_el| {
         set_is_ready.set(true);
@@ -71,14 +98,18 @@ pub fn Layer(
             canvas_el.set_width(width);
             canvas_el.set_height(height);
             let options = js_sys::Object::new();
-            set_canvas.set(Some(Canvas::new(create_2d_context(canvas_el, options))));
+            set_canvas.set(Some((
+                Canvas::new(width, height),
+                Presenter::new(create_2d_context(canvas_el, options)),
+            )));
         }
     });
 
     use_raf_fn(move |raf_args| {
         set_canvas.update(|c| {
-            let c = c.as_mut().unwrap();
+            let (c, presenter) = c.as_mut().unwrap();
             draw(c, raf_args);
+            presenter.present(c);
         });
     });
 
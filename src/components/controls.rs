@@ -1,17 +1,24 @@
 use crate::{
     app::GolContext,
-    components::{Button, ButtonVariant, Divider, Icon, Surface, Tooltip, TooltipTrigger},
+    components::{
+        Button, ButtonVariant, Divider, HistoryPanel, Icon, Popover, PopoverPlacement,
+        PopoverTrigger, Surface, Tooltip, TooltipTrigger,
+    },
+    history::Action,
 };
 use leptos::prelude::*;
 
 #[component]
 pub fn Controls() -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
     let GolContext {
         universe,
         is_ticking,
         tps,
+        run_start,
         ..
-    } = use_context::<GolContext>().unwrap();
+    } = ctx;
+    let (is_history_open, set_is_history_open) = signal(false);
 
     view! {
         <Surface class="pointer-events-auto flex overflow-hidden">
@@ -45,6 +52,22 @@ pub fn Controls() -> impl IntoView {
                 </Button>
                 <Tooltip>Redo</Tooltip>
             </TooltipTrigger>
+            <TooltipTrigger>
+                <PopoverTrigger is_open=is_history_open set_is_open=set_is_history_open>
+                    <Button
+                        variant=ButtonVariant::Icon
+                        on_press=move || {
+                            set_is_history_open.update(|open| *open = !*open);
+                        }
+                    >
+                        <Icon icon=icondata::LuHistory />
+                    </Button>
+                    <Popover placement=PopoverPlacement::Top>
+                        <HistoryPanel />
+                    </Popover>
+                </PopoverTrigger>
+                <Tooltip>History</Tooltip>
+            </TooltipTrigger>
             <Divider />
             <TooltipTrigger>
                 <Button
@@ -53,7 +76,8 @@ pub fn Controls() -> impl IntoView {
                         if universe.with(|u| u.step <= 0) {
                             tps.update(|tps| *tps /= 2.0);
                         } else {
-                            universe.update(|u| { u.step = (u.step - 1).max(0) })
+                            universe
+                                .update(|u| { u.step = (u.step.min(u.max_step()) - 1).max(0) })
                         }
                     }
                 >
@@ -61,6 +85,16 @@ pub fn Controls() -> impl IntoView {
                 </Button>
                 <Tooltip>Decrease Speed</Tooltip>
             </TooltipTrigger>
+            <TooltipTrigger>
+                <Button
+                    variant=ButtonVariant::Icon
+                    disabled=Signal::derive_local(move || run_start.with(Option::is_none))
+                    on_press=move || ctx.reset()
+                >
+                    <Icon icon=icondata::LuSkipBack />
+                </Button>
+                <Tooltip>Reset (R)</Tooltip>
+            </TooltipTrigger>
             <TooltipTrigger>
                 <Button
                     variant=ButtonVariant::Icon
@@ -78,13 +112,30 @@ pub fn Controls() -> impl IntoView {
                 </Button>
                 <Tooltip>{move || if is_ticking.get() { "Pause" } else { "Play" }}</Tooltip>
             </TooltipTrigger>
+            <TooltipTrigger>
+                <Button
+                    variant=ButtonVariant::Icon
+                    disabled=Signal::derive_local(move || universe.with(|u| !u.can_step_back()))
+                    on_press=move || {
+                        is_ticking.set(false);
+                        universe
+                            .update(|u| {
+                                u.push_snapshot(Action::Run);
+                                u.step_back();
+                            })
+                    }
+                >
+                    <Icon icon=icondata::LuStepBack />
+                </Button>
+                <Tooltip>Step Back</Tooltip>
+            </TooltipTrigger>
             <TooltipTrigger>
                 <Button
                     variant=ButtonVariant::Icon
                     on_press=move || {
+                        ctx.begin_run();
                         universe
                             .update(|u| {
-                                u.push_snapshot();
                                 u.step();
                             })
                     }
@@ -97,13 +148,13 @@ pub fn Controls() -> impl IntoView {
                 <Button
                     variant=ButtonVariant::Icon
                     disabled=Signal::derive_local(move || {
-                        universe.with(|u| u.step >= u.level() as i32 - 2)
+                        universe.with(|u| u.step >= u.max_step())
                     })
                     on_press=move || {
                         if tps.get() < 16.0 {
                             tps.update(|tps| *tps *= 2.0);
                         } else {
-                            universe.update(|u| { u.step = (u.step + 1).min(u.level() as i32 - 2) })
+                            universe.update(|u| { u.step = (u.step + 1).min(u.max_step()) })
                         }
                     }
                 >
@@ -111,6 +162,37 @@ pub fn Controls() -> impl IntoView {
                 </Button>
                 <Tooltip>Increase Speed</Tooltip>
             </TooltipTrigger>
+            <Divider />
+            <Scrubber />
         </Surface>
     }
 }
+
+#[component]
+fn Scrubber() -> impl IntoView {
+    let GolContext {
+        universe,
+        is_ticking,
+        ..
+    } = use_context::<GolContext>().unwrap();
+    let range = Memo::new(move |_| universe.with(|u| u.timeline_range()));
+
+    view! {
+        <div class="flex items-center px-3">
+            <input
+                r#type="range"
+                class="w-32 sm:w-48 accent-white"
+                disabled=move || range.get().0 == range.get().1
+                min=move || range.get().0.to_string()
+                max=move || range.get().1.to_string()
+                prop:value=move || universe.with(|u| u.generation).to_string()
+                on:input=move |e| {
+                    if let Ok(generation) = event_target_value(&e).parse::<u64>() {
+                        is_ticking.set(false);
+                        universe.update(|u| u.seek(generation));
+                    }
+                }
+            />
+        </div>
+    }
+}
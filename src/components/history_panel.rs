@@ -0,0 +1,66 @@
+use crate::{app::GolContext, components::Surface};
+use leptos::prelude::*;
+
+// branches past this depth are drawn at the same indent
+const MAX_INDENT: usize = 8;
+
+#[component]
+pub fn HistoryPanel() -> impl IntoView {
+    let GolContext {
+        universe,
+        is_ticking,
+        ..
+    } = use_context::<GolContext>().unwrap();
+    let rows = Memo::new(move |_| {
+        universe.with(|u| {
+            u.history
+                .walk()
+                .into_iter()
+                .map(|(i, depth)| {
+                    (
+                        i,
+                        depth,
+                        u.snapshot_label(i),
+                        u.snapshot_generation(i),
+                        i == u.history.current,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    view! {
+        <Surface class="mb-2 w-64 max-h-80 overflow-y-auto flex flex-col py-1">
+            {move || {
+                rows.get()
+                    .into_iter()
+                    .map(|(i, depth, label, generation, is_current)| {
+                        view! {
+                            <button
+                                class=if is_current {
+                                    "flex items-center gap-2 px-3 py-1 text-sm text-left bg-neutral-700"
+                                } else {
+                                    "flex items-center gap-2 px-3 py-1 text-sm text-left hover:bg-neutral-800"
+                                }
+                                on:click=move |_| {
+                                    is_ticking.set(false);
+                                    universe.update(|u| u.checkout(i));
+                                }
+                            >
+                                <span
+                                    class="truncate"
+                                    style:padding-left=format!("{}rem", depth.min(MAX_INDENT) as f64 * 0.75)
+                                >
+                                    {label}
+                                </span>
+                                <span class="ml-auto text-neutral-400 tabular-nums">
+                                    {format!("gen {}", generation)}
+                                </span>
+                            </button>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </Surface>
+    }
+}
@@ -2,6 +2,7 @@ use super::create_2d_context;
 use crate::components::{Layer, Stage};
 use crate::draw::{self, Viewport};
 use crate::parse::rle;
+use crate::rule::LifeRule;
 use crate::universe::step_grid;
 use leptos::html;
 use leptos::prelude::*;
@@ -50,7 +51,7 @@ pub fn LoadingCanvasProvider(children: Children) -> impl IntoView {
                         }
                         grid.update_value(|grid| {
                             let mut stepped = grid.clone();
-                            step_grid(grid, &mut stepped);
+                            step_grid(grid, &mut stepped, &LifeRule::LIFE);
                             std::mem::swap(grid, &mut stepped);
                             prev_tick.set_value(now);
                             let mut vp = Viewport::new();
@@ -6,12 +6,14 @@ pub mod button;
 pub mod canvas;
 pub mod controls;
 pub mod dialog;
+pub mod history_panel;
 pub mod input;
 pub mod loading;
 pub mod paste_layer;
 pub mod pattern_card;
 pub mod pattern_library;
 pub mod popover;
+pub mod restore_prompt;
 pub mod selection_layer;
 pub mod selection_menu;
 pub mod selection_overlay;
@@ -28,12 +30,14 @@ pub use crate::components::button::*;
 pub use crate::components::canvas::*;
 pub use crate::components::controls::*;
 pub use crate::components::dialog::*;
+pub use crate::components::history_panel::*;
 pub use crate::components::input::*;
 pub use crate::components::loading::*;
 pub use crate::components::paste_layer::*;
 pub use crate::components::pattern_card::*;
 pub use crate::components::pattern_library::*;
 pub use crate::components::popover::*;
+pub use crate::components::restore_prompt::*;
 pub use crate::components::selection_layer::*;
 pub use crate::components::selection_menu::*;
 pub use crate::components::selection_overlay::*;
@@ -1,4 +1,4 @@
-use leptos::{logging, prelude::*};
+use leptos::prelude::*;
 
 use crate::{app::GolContext, components::Layer, draw, universe::Universe};
 
@@ -46,7 +46,6 @@ pub fn PasteLayer() -> impl IntoView {
             }
             if !is_pasting.get() {
                 c.clear();
-                c.draw();
                 is_paste_canvas_dirty.set_value(false);
                 return;
             }
@@ -39,7 +39,6 @@ pub fn PatternCard(#[prop(into)] pattern: Signal<PatternMetadata, LocalStorage>)
                             if let Some(Ok(rle)) = pattern_rle.get() {
                                 let _ = draw::draw_rle(c, rle);
                                 set_is_ready.set(true);
-                                c.draw();
                                 is_dirty.set_value(false);
                             }
                         } />
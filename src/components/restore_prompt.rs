@@ -0,0 +1,104 @@
+use crate::{
+    app::{GolContext, SESSION_KEY, load_autosaved_session},
+    components::{Button, ButtonVariant, Dialog, PopoverTrigger, use_toast},
+    session::Session,
+    utils::local_storage,
+};
+use leptos::prelude::*;
+
+// offers the autosaved session from the last visit, if any.
+// on_close runs once, with whether it was restored
+#[component]
+pub fn RestorePrompt(#[prop(into)] on_close: Callback<bool>) -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
+    let logging = use_toast();
+    let (is_open, set_is_open) = signal(false);
+    let saved = LocalResource::new(load_autosaved_session);
+    let session = StoredValue::new_local(None::<Session>);
+
+    Effect::new(move |_| {
+        let Some(saved) = saved.get() else {
+            return;
+        };
+        match saved {
+            Some(saved) => {
+                session.set_value(Some(saved));
+                set_is_open.set(true);
+            }
+            None => on_close.run(false),
+        }
+    });
+    let close = move |restore: bool| {
+        let Some(session) = session.try_update_value(Option::take).flatten() else {
+            return;
+        };
+        set_is_open.set(false);
+        if !restore {
+            if let Some(storage) = local_storage() {
+                let _ = storage.remove_item(SESSION_KEY);
+            }
+            on_close.run(false);
+            return;
+        }
+        match ctx.restore_session(&session) {
+            Ok(()) => {
+                logging.log("Session restored!");
+                on_close.run(true);
+            }
+            Err(e) => {
+                logging.error(&format!("Couldn't restore session: {}", e));
+                on_close.run(false);
+            }
+        }
+    };
+    // dismissing the dialog discards the session too
+    Effect::new(move |_| {
+        if !is_open.get() {
+            close(false);
+        }
+    });
+
+    let description = move || {
+        session.with_value(|s| {
+            s.as_ref().map(|s| {
+                let name = if s.name.is_empty() {
+                    "your pattern"
+                } else {
+                    &s.name
+                };
+                format!(
+                    "Pick up {} at generation {} where you left off?",
+                    name, s.generation
+                )
+            })
+        })
+    };
+
+    view! {
+        <PopoverTrigger is_open=is_open set_is_open=set_is_open>
+            <Dialog>
+                <h2 class="text-lg font-bold text-center">RESTORE SESSION</h2>
+                <div class="border-t border-neutral-800 w-full" />
+                <div class="flex flex-col gap-4">
+                    <p>{description}</p>
+                    <div class="w-full flex justify-end gap-2">
+                        <Button
+                            variant=ButtonVariant::Secondary
+                            class="rounded-md"
+                            on_press=move || close(false)
+                        >
+                            DISCARD
+                        </Button>
+                        <Button
+                            variant=ButtonVariant::Primary
+                            class="rounded-md"
+                            on_press=move || close(true)
+                        >
+                            RESTORE
+                        </Button>
+                    </div>
+                </div>
+            </Dialog>
+        </PopoverTrigger>
+    }
+}
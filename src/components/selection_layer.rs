@@ -35,7 +35,6 @@ pub fn SelectionLayer() -> impl IntoView {
                     0x0000FF7F,
                 )
             }
-            c.draw();
             is_selection_dirty.set_value(false);
         } />
     }
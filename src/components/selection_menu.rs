@@ -1,22 +1,33 @@
 use crate::{
     app::GolContext,
-    components::{Button, ButtonVariant, Divider, Icon, Surface, use_toast},
+    components::{
+        Button, ButtonVariant, Dialog, Divider, Icon, PopoverTrigger, Surface, SvgForm, use_toast,
+    },
+    draw::{self, Colors},
+    history::Action,
     parse::rle,
+    utils::download_file,
 };
 use leptos::prelude::*;
 use leptos_use::{UseClipboardReturn, use_clipboard};
 use rand::Rng;
 
+// generations followed before giving up on naming the selection
+const APGCODE_LIMIT: u64 = 1 << 12;
+
 #[component]
 pub fn SelectionMenu() -> impl IntoView {
     let GolContext {
         universe,
+        name,
         selection_rect,
         ..
     } = use_context::<GolContext>().unwrap();
 
     let UseClipboardReturn { copy, .. } = use_clipboard();
+    let copy = StoredValue::new(copy);
     let logging = use_toast();
+    let (is_svg_open, set_is_svg_open) = signal(false);
     view! {
         <Surface class="pointer-events-auto flex overflow-hidden">
             <Button
@@ -26,7 +37,7 @@ pub fn SelectionMenu() -> impl IntoView {
                         let mut rng = rand::rng();
                         universe
                             .update(|u| {
-                                u.push_snapshot();
+                                u.push_snapshot(Action::Randomize);
                                 for y in y1..=y2 {
                                     for x in x1..=x2 {
                                         u.set(x, y, rng.random_bool(0.5) as u8);
@@ -46,7 +57,7 @@ pub fn SelectionMenu() -> impl IntoView {
                     if let Some((x1, y1, x2, y2)) = selection_rect.get() {
                         universe
                             .update(|u| {
-                                u.push_snapshot();
+                                u.push_snapshot(Action::Clear);
                                 u.clear_rect(x1, y1, x2, y2);
                             });
                     }
@@ -68,8 +79,10 @@ pub fn SelectionMenu() -> impl IntoView {
                                     y1,
                                     x2,
                                     y2,
+                                    &u.rule,
+                                    u.grid.as_ref(),
                                 );
-                                copy(&rle);
+                                copy.read_value()(&rle);
                                 logging.log("Copied RLE to clipboard!");
                             });
                     }
@@ -77,6 +90,65 @@ pub fn SelectionMenu() -> impl IntoView {
             >
                 <Icon icon=icondata::LuCopy />
             </Button>
+            <Divider />
+            <Button
+                variant=ButtonVariant::Icon
+                on_press=move || {
+                    if let Some((x1, y1, x2, y2)) = selection_rect.get() {
+                        match universe
+                            .try_update_untracked(|u| {
+                                u.apgcode_in_rect(x1, y1, x2, y2, APGCODE_LIMIT)
+                            })
+                            .flatten()
+                        {
+                            Some(apgcode) => {
+                                copy.read_value()(&apgcode);
+                                logging.log(&format!("Copied {} to clipboard!", apgcode));
+                            }
+                            None => logging.error("No period found for the selection"),
+                        }
+                    }
+                }
+            >
+                <Icon icon=icondata::LuHash />
+            </Button>
+            <Divider />
+            <Button
+                variant=ButtonVariant::Icon
+                on_press=move || {
+                    if let Some((x1, y1, x2, y2)) = selection_rect.get() {
+                        universe
+                            .with(|u| {
+                                let cell_size = draw::fit_cell_size(
+                                    x2 - x1 + 1,
+                                    y2 - y1 + 1,
+                                    draw::IMAGE_CELL_SIZE,
+                                    draw::MAX_IMAGE_SIZE,
+                                );
+                                let png = draw::render_rect(u, x1, y1, x2, y2, cell_size, &Colors::default()).to_png();
+                                download_file(&format!("{} (selection).png", name.get()), &png);
+                            });
+                    }
+                }
+            >
+                <Icon icon=icondata::LuImageDown />
+            </Button>
+            <Divider />
+            <PopoverTrigger is_open=is_svg_open set_is_open=set_is_svg_open>
+                <Button
+                    variant=ButtonVariant::Icon
+                    on_press=move || {
+                        set_is_svg_open.set(true);
+                    }
+                >
+                    <Icon icon=icondata::LuFileImage />
+                </Button>
+                <Dialog>
+                    <h2 class="text-lg font-bold text-center">EXPORT SVG</h2>
+                    <div class="border-t border-neutral-800 w-full" />
+                    <SvgForm close=move || set_is_svg_open.set(false) />
+                </Dialog>
+            </PopoverTrigger>
         // <Divider />
         // <Button
         // variant=ButtonVariant::Icon
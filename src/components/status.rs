@@ -1,14 +1,140 @@
 use crate::{
+    analysis::{Classification, PatternClass, Periodicity},
     app::{GolContext, use_fit_universe},
-    components::Dialog,
+    components::{Button, ButtonVariant, Dialog, PopoverTrigger},
+    quadtree,
 };
 use leptos::{html, prelude::*};
 use leptos_use::{UseTimeoutFnReturn, use_timeout_fn};
 use tailwind_fuse::tw_merge;
 
+// generations followed before giving up on finding a period
+const ANALYSIS_LIMIT: u64 = 1 << 12;
+// generations the classifier runs before settling on growth or unknown
+const CLASSIFY_LIMIT: u64 = 1 << 12;
+
+// hh:mm:ss in local time
+fn clock_time(ms: f64) -> String {
+    let date = js_sys::Date::new(&ms.into());
+    format!(
+        "{:02}:{:02}:{:02}",
+        date.get_hours(),
+        date.get_minutes(),
+        date.get_seconds()
+    )
+}
+
+#[component]
+fn Row(#[prop(into)] label: String, #[prop(into)] value: Signal<String>) -> impl IntoView {
+    view! {
+        <div class="flex justify-between gap-8">
+            <span class="text-neutral-400">{label}</span>
+            <span>{move || value.get()}</span>
+        </div>
+    }
+}
+
 #[component]
 fn PatternInfo() -> impl IntoView {
-    view! { <div></div> }
+    let GolContext { universe, name, .. } = use_context::<GolContext>().unwrap();
+    // (generation, root) the results were found for, so they are dropped once the pattern changes
+    let key = move || universe.with(|u| (u.generation, u.root()));
+    type Key = (u64, quadtree::NodeRef);
+    let (analysis, set_analysis) = signal(None::<(Key, Option<(Periodicity, Option<String>)>)>);
+    let (classification, set_classification) = signal(None::<(Key, Classification)>);
+    let current_analysis = move || {
+        analysis
+            .get()
+            .and_then(|(k, result)| (k == key()).then_some(result))
+    };
+    let current_classification = move || {
+        classification
+            .get()
+            .and_then(|(k, result)| (k == key()).then_some(result))
+    };
+
+    view! {
+        <div class="flex flex-col gap-1 text-sm min-w-64">
+            <Row label="Name" value=Signal::derive(move || name.get()) />
+            <Row label="Rule" value=Signal::derive(move || universe.with(|u| u.rule.to_string())) />
+            <Row
+                label="Population"
+                value=Signal::derive(move || universe.with(|u| u.population().to_string()))
+            />
+            {move || match current_analysis() {
+                None => ().into_any(),
+                Some(None) => {
+                    view! {
+                        <p class="text-neutral-400 pt-2">
+                            {format!("No period found within {} generations", ANALYSIS_LIMIT)}
+                        </p>
+                    }
+                        .into_any()
+                }
+                Some(Some((p, _))) if p.population == 0 => {
+                    view! { <Row label="Dies at gen" value=p.start.to_string() /> }.into_any()
+                }
+                Some(Some((p, apgcode))) => {
+                    view! {
+                        <Row label="Type" value=PatternClass::from(p).to_string() />
+                        <Row label="apgcode" value=apgcode.unwrap_or("—".to_owned()) />
+                        <Row label="Period" value=p.period.to_string() />
+                        <Row label="Speed" value=p.speed().unwrap_or("—".to_owned()) />
+                        <Row label="Displacement" value=format!("({}, {})", p.dx, p.dy) />
+                        <Row label="Starts at gen" value=p.start.to_string() />
+                    }
+                        .into_any()
+                }
+            }}
+            {move || {
+                current_classification()
+                    .map(|c| {
+                        view! {
+                            <Row label="Class" value=c.class.to_string() />
+                            <Row
+                                label="Confidence"
+                                value=format!("{:.0}%", c.confidence * 100.0)
+                            />
+                            <Row label="Generations run" value=c.generations.to_string() />
+                        }
+                    })
+            }}
+            <div class="w-full flex justify-end gap-2 pt-2">
+                <Button
+                    class="rounded-md"
+                    disabled=Signal::derive_local(move || current_classification().is_some())
+                    on_press=move || {
+                        let k = key();
+                        // the universe is left as it was, so nothing needs redrawing
+                        if let Some(result) = universe
+                            .try_update_untracked(|u| u.classify(CLASSIFY_LIMIT))
+                        {
+                            set_classification.set(Some((k, result)));
+                        }
+                    }
+                >
+                    CLASSIFY
+                </Button>
+                <Button
+                    variant=ButtonVariant::Primary
+                    class="rounded-md"
+                    disabled=Signal::derive_local(move || current_analysis().is_some())
+                    on_press=move || {
+                        let k = key();
+                        let result = universe
+                            .try_update_untracked(|u| {
+                                let p = u.find_period(ANALYSIS_LIMIT)?;
+                                Some((p, u.apgcode_for(p)))
+                            })
+                            .flatten();
+                        set_analysis.set(Some((k, result)));
+                    }
+                >
+                    ANALYZE
+                </Button>
+            </div>
+        </div>
+    }
 }
 
 #[component]
@@ -64,13 +190,16 @@ fn metric_string(n: f64) -> String {
 
 #[component]
 pub fn Status() -> impl IntoView {
+    let ctx = use_context::<GolContext>().unwrap();
     let GolContext {
         universe,
         name,
         cursor,
         viewport,
+        recorder,
+        run_start,
         ..
-    } = use_context::<GolContext>().unwrap();
+    } = ctx;
     let ratio = move || {
         let cell_size = viewport.get().cell_size;
         if cell_size < 1.0 {
@@ -80,15 +209,29 @@ pub fn Status() -> impl IntoView {
         }
     };
 
+    // a memo so running doesn't rebuild it every generation
+    let start_label = Memo::new(move |_| {
+        run_start.with(|start| {
+            start.as_ref().map(|start| {
+                format!(
+                    "Start: {} (gen {})",
+                    clock_time(start.time),
+                    universe.with(|u| u.snapshot_generation(start.snapshot)),
+                )
+            })
+        })
+    });
+
     let (is_renaming, set_is_renaming) = signal(false);
+    let (is_info_open, set_is_info_open) = signal(false);
     let UseTimeoutFnReturn {
         start,
         stop,
         is_pending,
         ..
     } = use_timeout_fn(
-        |_| {
-            // TODO: display info
+        move |_| {
+            set_is_info_open.set(true);
         },
         300.0,
     );
@@ -142,8 +285,49 @@ pub fn Status() -> impl IntoView {
                 }}
 
             </div>
+            <PopoverTrigger is_open=is_info_open set_is_open=set_is_info_open>
+                <Dialog>
+                    <h2 class="text-lg font-bold text-center">PATTERN INFO</h2>
+                    <div class="border-t border-neutral-800 w-full" />
+                    <PatternInfo />
+                </Dialog>
+            </PopoverTrigger>
             <div class="ml-auto inline-flex flex-wrap">
-                <Item>{move || format!("Step: {}", 1i64 << universe.with(|u| u.step))}</Item>
+                {move || {
+                    recorder
+                        .with(|r| {
+                            r.as_ref()
+                                .map(|r| {
+                                    let progress = format!(
+                                        "REC {}/{}",
+                                        r.frames(),
+                                        r.total_frames(),
+                                    );
+                                    view! {
+                                        // stops early and saves what was recorded so far
+                                        <Item
+                                            class="text-red-400"
+                                            on_press=move || ctx.finish_recording()
+                                        >
+                                            {progress}
+                                        </Item>
+                                        <Divider />
+                                    }
+                                })
+                        })
+                }}
+                {move || {
+                    start_label
+                        .get()
+                        .map(|label| {
+                            view! {
+                                // resets, like the button and the R key
+                                <Item on_press=move || ctx.reset()>{label}</Item>
+                                <Divider />
+                            }
+                        })
+                }}
+                <Item>{move || format!("Step: {}", 1i64 << universe.with(|u| u.step.min(u.max_step())))}</Item>
                 <Divider />
                 <Item>{move || format!("Gen: {}", universe.with(|u| u.generation))}</Item>
                 <Divider />
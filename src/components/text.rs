@@ -1,7 +1,15 @@
-use crate::{components::Link, parse::get_index};
-use js_sys::RegExp;
+use crate::components::Link;
+use js_sys::{Reflect, RegExp, wasm_bindgen::JsValue};
 use leptos::prelude::*;
 
+fn get_index(captures: &JsValue) -> usize {
+    Reflect::get(captures, &JsValue::from("index"))
+        .ok()
+        .unwrap()
+        .as_f64()
+        .unwrap() as usize
+}
+
 thread_local! {
     static CONWAY_LIFE_LINK_RE: RegExp = RegExp::new(r"\b(?:https?://)?((?:www\.)?conwaylife\.com(\S*))\b", "");
 }
@@ -1,40 +1,37 @@
 use crate::{
     parse::rle::{self, PatternMetadata},
     quadtree::{Node, NodeKind, NodeRef},
+    rule::BoundedGrid,
     universe::Universe,
 };
-use web_sys::{CanvasRenderingContext2d, ImageData, wasm_bindgen::Clamped};
+use serde::{Deserialize, Serialize};
 
+// rgba pixels, put on screen by components::canvas or encoded as images
 pub struct Canvas {
-    pub ctx: CanvasRenderingContext2d,
     pub buffer: Vec<u8>,
     pub width: u32,
     pub height: u32,
+    // set whenever the buffer changes, so unchanged frames aren't presented again
+    pub is_dirty: bool,
 }
 impl Canvas {
-    pub fn new(ctx: CanvasRenderingContext2d) -> Self {
-        let (width, height) = (
-            ctx.canvas().unwrap().width(),
-            ctx.canvas().unwrap().height(),
-        );
+    pub fn new(width: u32, height: u32) -> Self {
         let buffer_size = (width * height * 4) as usize;
 
         Self {
-            ctx,
             buffer: vec![0; buffer_size],
             width,
             height,
+            is_dirty: true,
         }
     }
-    pub fn resize(&mut self) {
-        self.width = self.ctx.canvas().unwrap().width();
-        self.height = self.ctx.canvas().unwrap().height();
-        let buffer_size = (self.width * self.height * 4) as usize;
-        self.buffer = vec![0; buffer_size];
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = Self::new(width, height);
     }
 
     pub fn clear(&mut self) {
         self.buffer.fill(0);
+        self.is_dirty = true;
     }
     pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u32) {
         // 0xRRGGBBAA
@@ -51,6 +48,7 @@ impl Canvas {
             (y + height).min(self.height as i32),
         );
 
+        self.is_dirty = true;
         let stride = (self.width as i32) * 4;
         let row_byte_start = x1 * 4;
         let row_byte_end = x2 * 4;
@@ -89,19 +87,20 @@ impl Canvas {
 
         self.fill_rect(c_x1, c_y1, c_x2 - c_x1, c_y2 - c_y1, color);
     }
-    pub fn draw(&self) {
-        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
-            Clamped(&self.buffer),
-            self.width,
-            self.height,
-        );
-        if let Ok(data) = image_data {
-            self.ctx.put_image_data(&data, 0.0, 0.0).unwrap();
-        }
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // writing into a vec only fails on sizes png can't hold
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&self.buffer).unwrap();
+        writer.finish().unwrap();
+        png
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Viewport {
     pub origin: (f64, f64), // top left
     pub cell_size: f64,
@@ -178,6 +177,23 @@ impl Viewport {
 }
 
 const ALIVE_COLOR: u32 = 0xFFFFFFFF;
+const BOUNDS_COLOR: u32 = 0x737373FF;
+// dying states of Generations rules fade from `color` towards black
+pub(crate) fn state_color(color: u32, state: u8, states: u16) -> u32 {
+    if state <= 1 {
+        return color;
+    }
+    let (num, den) = (
+        states.saturating_sub(state as u16) as u32,
+        (states - 1) as u32,
+    );
+    let mut res = color & 0xFF;
+    for shift in [8, 16, 24] {
+        let channel = (color >> shift) & 0xFF;
+        res |= (channel * num / den) << shift;
+    }
+    res
+}
 fn _draw_node(
     canvas: &mut Canvas,
     viewport: &Viewport,
@@ -210,19 +226,15 @@ fn _draw_node(
 
     match &node.data {
         NodeKind::Leaf(leaf) => {
-            for (i, row) in leaf.iter().enumerate() {
-                for (j, cell) in row.iter().enumerate() {
-                    if *cell != 0 {
-                        canvas.fill_rect_with_viewport(
-                            viewport,
-                            (x + j as i64) as f64,
-                            (y + i as i64) as f64,
-                            1.0,
-                            1.0,
-                            color,
-                        );
-                    }
-                }
+            for (j, i, cell) in leaf.iter() {
+                canvas.fill_rect_with_viewport(
+                    viewport,
+                    (x + j as i64) as f64,
+                    (y + i as i64) as f64,
+                    1.0,
+                    1.0,
+                    state_color(color, cell, universe.rule.states()),
+                );
             }
         }
         NodeKind::Branch(children) => {
@@ -233,7 +245,24 @@ fn _draw_node(
         }
     };
 }
+// 1px outline just outside the grid's cells
+fn draw_bounds(canvas: &mut Canvas, viewport: &Viewport, grid: &BoundedGrid, color: u32) {
+    let (left, top, right, bottom) = grid.bounds();
+    let (x1, y1) = viewport.to_canvas_coords(left as f64, top as f64);
+    let (x2, y2) = viewport.to_canvas_coords((right + 1) as f64, (bottom + 1) as f64);
+    // keep edges far off canvas from overflowing
+    let clamp_x = |x: i32| x.clamp(-2, canvas.width as i32 + 2);
+    let clamp_y = |y: i32| y.clamp(-2, canvas.height as i32 + 2);
+    let (x1, y1, x2, y2) = (clamp_x(x1) - 1, clamp_y(y1) - 1, clamp_x(x2), clamp_y(y2));
+    canvas.fill_rect(x1, y1, x2 - x1 + 1, 1, color);
+    canvas.fill_rect(x1, y2, x2 - x1 + 1, 1, color);
+    canvas.fill_rect(x1, y1, 1, y2 - y1, color);
+    canvas.fill_rect(x2, y1, 1, y2 - y1, color);
+}
 pub fn draw_node(canvas: &mut Canvas, viewport: &Viewport, universe: &Universe, color: u32) {
+    if let Some(grid) = &universe.grid {
+        draw_bounds(canvas, viewport, grid, BOUNDS_COLOR);
+    }
     let half = 1i64 << (universe.level() - 1);
     _draw_node(
         canvas,
@@ -244,10 +273,10 @@ pub fn draw_node(canvas: &mut Canvas, viewport: &Viewport, universe: &Universe,
         -half,
         color,
     );
-    canvas.draw();
 }
 pub fn draw_rle(canvas: &mut Canvas, rle: String) -> Result<(), ()> {
-    let (PatternMetadata { width, height, .. }, _) = rle::parse_metadata(&rle, "", "")?;
+    let (PatternMetadata { width, height, .. }, _) =
+        rle::parse_metadata(&rle, "", "").map_err(|_| ())?;
     let mut vp = Viewport::new();
     vp.fit_rect(
         0.0,
@@ -264,7 +293,7 @@ pub fn draw_rle(canvas: &mut Canvas, rle: String) -> Result<(), ()> {
         return Err(());
     }
 
-    for (x, y) in rle::iter_alive(&rle).unwrap() {
+    for (x, y, _) in rle::iter_alive(&rle).unwrap() {
         let (nx, ny) = (x >> l2 << l2, y >> l2 << l2);
         canvas.fill_rect_with_viewport(
             &vp,
@@ -276,7 +305,6 @@ pub fn draw_rle(canvas: &mut Canvas, rle: String) -> Result<(), ()> {
         );
     }
 
-    canvas.draw();
     Ok(())
 }
 pub fn draw_grid(canvas: &mut Canvas, viewport: &Viewport, grid: &[Vec<u8>]) {
@@ -287,6 +315,63 @@ pub fn draw_grid(canvas: &mut Canvas, viewport: &Viewport, grid: &[Vec<u8>]) {
             }
         }
     }
+}
+
+const BACKGROUND_COLOR: u32 = 0x000000FF;
+// pixels per cell in exported images, unless that makes them too large
+pub const IMAGE_CELL_SIZE: f64 = 8.0;
+// largest side of an exported image, so huge patterns come out scaled down
+pub const MAX_IMAGE_SIZE: u32 = 4096;
+
+// 0xRRGGBBAA, like everything else drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Colors {
+    pub alive: u32,
+    pub background: u32,
+}
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            alive: ALIVE_COLOR,
+            background: BACKGROUND_COLOR,
+        }
+    }
+}
 
-    canvas.draw();
+// pixels per cell that keep a width by height cell rect within max_size
+pub fn fit_cell_size(width: i64, height: i64, cell_size: f64, max_size: u32) -> f64 {
+    cell_size.min(max_size as f64 / width.max(height).max(1) as f64)
+}
+// what the viewport shows on a canvas of the given size
+pub fn render_viewport(
+    universe: &Universe,
+    viewport: &Viewport,
+    width: u32,
+    height: u32,
+    colors: &Colors,
+) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    canvas.fill_rect(0, 0, width as i32, height as i32, colors.background);
+    draw_node(&mut canvas, viewport, universe, colors.alive);
+    canvas
+}
+// the cells from (x1, y1) to (x2, y2) at cell_size pixels each
+pub fn render_rect(
+    universe: &Universe,
+    x1: i64,
+    y1: i64,
+    x2: i64,
+    y2: i64,
+    cell_size: f64,
+    colors: &Colors,
+) -> Canvas {
+    let viewport = Viewport {
+        origin: (x1 as f64, y1 as f64),
+        cell_size,
+    };
+    let (width, height) = (
+        ((x2 - x1 + 1) as f64 * cell_size).ceil().max(1.0) as u32,
+        ((y2 - y1 + 1) as f64 * cell_size).ceil().max(1.0) as u32,
+    );
+    render_viewport(universe, &viewport, width, height, colors)
 }
@@ -0,0 +1,151 @@
+use crate::{quadtree::NodeRef, universe::Universe};
+use serde::{Deserialize, Serialize};
+
+// what led to a snapshot from its parent
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Start,
+    Toggle,
+    Paste,
+    Clear,
+    Randomize,
+    // playing, stepping or seeking, by however many generations it took
+    Run,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub root: NodeRef,
+    pub generation: u64,
+    pub step: i32,
+    pub action: Action,
+    pub parent: Option<usize>,
+    // the child redo goes to, the one last visited
+    pub redo: Option<usize>,
+}
+
+// undo tree, snapshots only ever get added so an index stays valid.
+// the current snapshot is live: the universe edits its root in place,
+// and its generation and step are written back when leaving it
+pub struct History {
+    pub snapshots: Vec<Snapshot>,
+    pub current: usize,
+}
+
+impl History {
+    pub fn new(root: NodeRef) -> Self {
+        Self {
+            snapshots: vec![Snapshot {
+                root,
+                generation: 0,
+                step: 0,
+                action: Action::Start,
+                parent: None,
+                redo: None,
+            }],
+            current: 0,
+        }
+    }
+    // snapshots depth first from the start, with their depth in the tree
+    pub fn walk(&self) -> Vec<(usize, usize)> {
+        let mut children = vec![Vec::new(); self.snapshots.len()];
+        for (i, snapshot) in self.snapshots.iter().enumerate() {
+            if let Some(parent) = snapshot.parent {
+                children[parent].push(i);
+            }
+        }
+        let mut walk = Vec::with_capacity(self.snapshots.len());
+        let mut stack = vec![(0, 0)];
+        while let Some((i, depth)) = stack.pop() {
+            walk.push((i, depth));
+            // the first child stays on the same level like a timeline, later ones branch off
+            for (k, &child) in children[i].iter().enumerate().rev() {
+                stack.push((child, if k == 0 { depth } else { depth + 1 }));
+            }
+        }
+        walk
+    }
+}
+
+impl Universe {
+    fn save_snapshot(&mut self) {
+        let (generation, step) = (self.generation, self.step);
+        let snapshot = &mut self.history.snapshots[self.history.current];
+        (snapshot.generation, snapshot.step) = (generation, step);
+    }
+    // call before the action, which then edits the new snapshot
+    pub fn push_snapshot(&mut self, action: Action) {
+        self.save_snapshot();
+        let parent = self.history.current;
+        let snapshot = Snapshot {
+            action,
+            parent: Some(parent),
+            redo: None,
+            ..self.history.snapshots[parent].clone()
+        };
+        self.history.snapshots.push(snapshot);
+        self.history.current = self.history.snapshots.len() - 1;
+        self.history.snapshots[parent].redo = Some(self.history.current);
+    }
+    // restores any snapshot, redo then leads back along the way to it
+    pub fn checkout(&mut self, i: usize) {
+        if i >= self.history.snapshots.len() || i == self.history.current {
+            return;
+        }
+        self.save_snapshot();
+        let mut child = i;
+        while let Some(parent) = self.history.snapshots[child].parent {
+            self.history.snapshots[parent].redo = Some(child);
+            child = parent;
+        }
+        self.history.current = i;
+        let snapshot = &self.history.snapshots[i];
+        (self.generation, self.step) = (snapshot.generation, snapshot.step);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.snapshots[self.history.current]
+            .parent
+            .is_some()
+    }
+    pub fn undo(&mut self) {
+        if let Some(parent) = self.history.snapshots[self.history.current].parent {
+            self.checkout(parent);
+        }
+    }
+    pub fn can_redo(&self) -> bool {
+        self.history.snapshots[self.history.current].redo.is_some()
+    }
+    pub fn redo(&mut self) {
+        if let Some(child) = self.history.snapshots[self.history.current].redo {
+            self.checkout(child);
+        }
+    }
+
+    pub fn snapshot_generation(&self, i: usize) -> u64 {
+        if i == self.history.current {
+            self.generation
+        } else {
+            self.history.snapshots[i].generation
+        }
+    }
+    pub fn snapshot_label(&self, i: usize) -> String {
+        let snapshot = &self.history.snapshots[i];
+        match snapshot.action {
+            Action::Start => "start".to_owned(),
+            Action::Toggle => "toggle".to_owned(),
+            Action::Paste => "paste".to_owned(),
+            Action::Clear => "clear".to_owned(),
+            Action::Randomize => "randomize".to_owned(),
+            Action::Run => {
+                let from = snapshot.parent.map_or(0, |p| self.snapshot_generation(p));
+                let to = self.snapshot_generation(i);
+                if to >= from {
+                    format!("run {} gens", to - from)
+                } else {
+                    format!("rewind {} gens", from - to)
+                }
+            }
+        }
+    }
+}
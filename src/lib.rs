@@ -1,13 +1,21 @@
 // faster than allocating Vec for partitions
 #![feature(iter_partition_in_place)]
+pub mod analysis;
 pub mod app;
 pub mod arena;
+pub mod cache;
 pub mod components;
 pub mod draw;
+pub mod history;
 pub mod layout;
+pub mod ltl;
 pub mod meta;
 pub mod parse;
 pub mod quadtree;
+pub mod record;
+pub mod rule;
+pub mod session;
+pub mod svg;
+pub mod timeline;
 pub mod universe;
 pub mod utils;
-
@@ -0,0 +1,113 @@
+use crate::{
+    rule::{LtlRule, Neighborhood},
+    universe::{InsertMode, Universe},
+};
+
+// inclusive box sums over a grid of 0/1 values
+struct SummedArea {
+    sums: Vec<u32>,
+    width: usize,
+}
+
+impl SummedArea {
+    fn new(width: usize, height: usize, mut value: impl FnMut(usize, usize) -> u32) -> Self {
+        let stride = width + 1;
+        let mut sums = vec![0; stride * (height + 1)];
+        for y in 0..height {
+            let mut row = 0;
+            for x in 0..width {
+                row += value(x, y);
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+            }
+        }
+        Self { sums, width }
+    }
+
+    // cells in [x1, x2] x [y1, y2], clipped to the grid
+    fn query(&self, x1: i64, y1: i64, x2: i64, y2: i64) -> u32 {
+        let stride = self.width + 1;
+        let height = (self.sums.len() / stride - 1) as i64;
+        let (x1, y1) = (x1.max(0) as usize, y1.max(0) as usize);
+        let (x2, y2) = (
+            (x2 + 1).min(self.width as i64) as usize,
+            (y2 + 1).min(height) as usize,
+        );
+        if x1 >= x2 || y1 >= y2 {
+            return 0;
+        }
+        self.sums[y2 * stride + x2] + self.sums[y1 * stride + x1]
+            - self.sums[y1 * stride + x2]
+            - self.sums[y2 * stride + x1]
+    }
+}
+
+impl Universe {
+    // Larger than Life neighborhoods are too wide for HashLife's 3x3 base case,
+    // so each generation is computed directly over the pattern's bounding box
+    pub(crate) fn step_ltl(&mut self, rule: &LtlRule) {
+        let cells = self.iter_alive().collect::<Vec<_>>();
+        if cells.is_empty() {
+            return;
+        }
+        let r = rule.range as i64;
+        let (mut x1, mut y1, mut x2, mut y2) = (i64::MAX, i64::MAX, i64::MIN, i64::MIN);
+        for &(x, y, _) in &cells {
+            (x1, y1, x2, y2) = (x1.min(x), y1.min(y), x2.max(x), y2.max(y));
+        }
+        // births can only happen within range of a live cell
+        let (left, top) = (x1 - r, y1 - r);
+        let (width, height) = (
+            (x2 - x1 + 1 + 2 * r) as usize,
+            (y2 - y1 + 1 + 2 * r) as usize,
+        );
+        let mut grid = vec![vec![0u8; width]; height];
+        for &(x, y, state) in &cells {
+            grid[(y - top) as usize][(x - left) as usize] = state;
+        }
+
+        let live = |x: i64, y: i64| {
+            (0 <= x && x < width as i64 && 0 <= y && y < height as i64)
+                && grid[y as usize][x as usize] == 1
+        };
+        let h = height as i64 - 1;
+        // von Neumann neighborhoods are rotated by 45 degrees so the diamond becomes a box
+        // (u, v) = (x + y, x - y + h)
+        let sat = match rule.neighborhood {
+            Neighborhood::Moore => {
+                SummedArea::new(width, height, |x, y| live(x as i64, y as i64) as u32)
+            }
+            Neighborhood::VonNeumann => {
+                let size = width + height - 1;
+                SummedArea::new(size, size, |u, v| {
+                    let (u, v) = (u as i64, v as i64);
+                    let (x2, y2) = (u + v - h, u - v + h);
+                    (x2 % 2 == 0 && live(x2 / 2, y2 / 2)) as u32
+                })
+            }
+        };
+
+        let mut points = Vec::new();
+        for (y, row) in grid.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                let (x, y) = (x as i64, y as i64);
+                let (cx, cy) = match rule.neighborhood {
+                    Neighborhood::Moore => (x, y),
+                    Neighborhood::VonNeumann => (x + y, x - y + h),
+                };
+                let mut count = sat.query(cx - r, cy - r, cx + r, cy + r);
+                if !rule.middle && cell == 1 {
+                    count -= 1;
+                }
+                let state = rule.next(cell, count);
+                if state != 0 {
+                    points.push((x + left, y + top, state));
+                }
+            }
+        }
+
+        // set_points grows the root if the pattern has spread past it
+        self.clear();
+        let (right, bottom) = (left + width as i64 - 1, top + height as i64 - 1);
+        self.set_points(&points, left, top, right, bottom, &InsertMode::Or);
+    }
+}
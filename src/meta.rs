@@ -31,7 +31,7 @@ impl Universe {
 
         let off_points = rle::iter_alive(meta_off_rle)
             .unwrap()
-            .map(|p| (p.0 - 5, p.1 - 5))
+            .map(|p| (p.0 - 5, p.1 - 5, p.2))
             .collect::<Vec<_>>();
 
         let half = 1i64 << (self.level() - 1);
@@ -48,7 +48,7 @@ impl Universe {
 
         let on_points = rle::iter_alive(meta_on_rle)
             .unwrap()
-            .map(|p| (p.0 - 5, p.1 - 5))
+            .map(|p| (p.0 - 5, p.1 - 5, p.2))
             .collect::<Vec<_>>();
         self.set_points(
             &on_points,
@@ -86,7 +86,13 @@ impl Universe {
                 self.set_points(
                     &corner_points
                         .iter()
-                        .map(|(x, y)| (x + dx * META_CELL_SIZE - 5, y + dy * META_CELL_SIZE - 5))
+                        .map(|(x, y, state)| {
+                            (
+                                x + dx * META_CELL_SIZE - 5,
+                                y + dy * META_CELL_SIZE - 5,
+                                *state,
+                            )
+                        })
                         .collect::<Vec<_>>(),
                     dx * META_CELL_SIZE - 5,
                     dy * META_CELL_SIZE - 5,
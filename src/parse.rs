@@ -1,25 +1,103 @@
-use js_sys::{Reflect, wasm_bindgen::JsValue};
-
-pub fn get_index(captures: &JsValue) -> usize {
-    Reflect::get(captures, &JsValue::from("index"))
-        .ok()
-        .unwrap()
-        .as_f64()
-        .unwrap() as usize
+use crate::{rule::parse_rule, universe::Universe};
+use rle::{ParseError, ParseMode, ParsedRle};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Rle,
+    Macrocell,
+    Plaintext,
+    Life105,
+    Life106,
+    Apgcode,
 }
-pub mod rle {
-    use crate::universe::UniverseIterator;
 
-    use super::get_index;
-    use js_sys::RegExp;
-    use serde::{Deserialize, Serialize};
+impl Format {
+    // anything unrecognized is read as RLE so its errors make sense
+    pub fn detect(text: &str) -> Self {
+        let Some(first) = text.lines().map(str::trim).find(|line| !line.is_empty()) else {
+            return Self::Rle;
+        };
+        if first.starts_with("[M2]") {
+            Self::Macrocell
+        } else if apgcode::decode(text).is_some() {
+            Self::Apgcode
+        } else if first.starts_with("#Life 1.05") {
+            Self::Life105
+        } else if first.starts_with("#Life 1.06") {
+            Self::Life106
+        } else if first.starts_with('!') {
+            Self::Plaintext
+        } else if text.lines().any(rle::is_header) {
+            Self::Rle
+        } else if first.chars().all(|c| matches!(c, '.' | 'O' | '*')) {
+            Self::Plaintext
+        } else {
+            Self::Rle
+        }
+    }
+}
 
-    thread_local! {
-            static SECTION_RE: RegExp = RegExp::new(r"^#([a-zA-Z])(.*)$", "m");
-            static HEADER_RE: RegExp = RegExp::new(
-                r"^\s*x\s*=\s*(\d+)\s*,?\s*y\s*=\s*(\d+)\s*(?:,?\s*rule\s*=\s*(\S+)\s*)?$","m"
-        );
+// macrocell files are built straight into a universe, see macrocell::read
+pub fn parse(text: &str, mode: ParseMode) -> Result<ParsedRle, ParseError> {
+    match Format::detect(text) {
+        Format::Rle | Format::Macrocell => rle::parse(text, mode),
+        Format::Plaintext => plaintext::read(text, mode),
+        Format::Life105 => life::read_105(text, mode),
+        Format::Life106 => life::read_106(text, mode),
+        Format::Apgcode => {
+            let cells = apgcode::decode(text).unwrap_or_default();
+            let width = cells.iter().map(|&(x, _, _)| x + 1).max().unwrap_or(0);
+            let height = cells.iter().map(|&(_, y, _)| y + 1).max().unwrap_or(0);
+            Ok(ParsedRle {
+                metadata: rle::PatternMetadata {
+                    name: text.trim().to_owned(),
+                    path: "".to_owned(),
+                    comment: "".to_owned(),
+                    owner: None,
+                    width: width as u32,
+                    height: height as u32,
+                    rule: "23/3".to_owned(),
+                },
+                cells,
+                warnings: Vec::new(),
+            })
+        }
     }
+}
+
+// replaces the pattern and rule of the universe with a file in any format, returning the
+// warnings. the universe is left as it was if the file can't be read or its rule isn't supported
+pub fn load_universe(
+    text: &str,
+    mode: ParseMode,
+    u: &mut Universe,
+) -> Result<Vec<ParseError>, String> {
+    if Format::detect(text) == Format::Macrocell {
+        let (metadata, root) = macrocell::read(text, u).ok_or("Invalid macrocell")?;
+        let (rule, grid) =
+            parse_rule(&metadata.rule).ok_or(format!("Unsupported rule: {}", metadata.rule))?;
+        (u.rule, u.grid) = (rule, grid);
+        *u.root_mut() = root;
+        u.generation = metadata.generation;
+        return Ok(Vec::new());
+    }
+
+    let parsed = parse(text, mode).map_err(|e| e.to_string())?;
+    let (rule, grid) = parse_rule(&parsed.metadata.rule)
+        .ok_or(format!("Unsupported rule: {}", parsed.metadata.rule))?;
+    (u.rule, u.grid) = (rule, grid);
+    u.load_cells(&parsed.cells, parsed.metadata.width, parsed.metadata.height);
+    Ok(parsed.warnings)
+}
+
+pub mod rle {
+    use crate::{
+        rule::{BoundedGrid, Rule, parse_rule},
+        universe::UniverseIterator,
+    };
+
+    use serde::{Deserialize, Serialize};
+    use std::fmt;
 
     #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
     pub struct PatternMetadata {
@@ -32,105 +110,341 @@ pub mod rle {
         pub rule: String,
     }
 
+    // 1-based, with columns counted in characters
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub struct Position {
+        pub line: usize,
+        pub column: usize,
+    }
+
+    impl Position {
+        pub(super) fn at(text: &str, offset: usize) -> Self {
+            let before = &text[..offset];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            Self {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub enum ParseError {
+        MissingHeader,
+        InvalidHeader {
+            position: Position,
+        },
+        InvalidCount {
+            position: Position,
+            count: String,
+        },
+        UnknownTag {
+            position: Position,
+            tag: char,
+        },
+        // a state tag the rule has no state for
+        InvalidState {
+            position: Position,
+            state: u32,
+            states: u16,
+        },
+        OutOfBounds {
+            position: Position,
+            x: i64,
+            y: i64,
+            width: u32,
+            height: u32,
+        },
+        MissingEnd {
+            position: Position,
+        },
+        UnknownCell {
+            position: Position,
+            cell: char,
+        },
+        InvalidCoordinates {
+            position: Position,
+        },
+    }
+
+    impl ParseError {
+        pub fn position(&self) -> Option<Position> {
+            match self {
+                Self::MissingHeader => None,
+                Self::InvalidHeader { position }
+                | Self::InvalidCount { position, .. }
+                | Self::UnknownTag { position, .. }
+                | Self::InvalidState { position, .. }
+                | Self::OutOfBounds { position, .. }
+                | Self::MissingEnd { position }
+                | Self::UnknownCell { position, .. }
+                | Self::InvalidCoordinates { position } => Some(*position),
+            }
+        }
+        pub fn message(&self) -> String {
+            match self {
+                Self::MissingHeader => {
+                    "missing header, expected `x = <width>, y = <height>`".to_owned()
+                }
+                Self::InvalidHeader { .. } => {
+                    "invalid header, expected `x = <width>, y = <height>, rule = <rule>`".to_owned()
+                }
+                Self::InvalidCount { count, .. } => format!("invalid run count `{}`", count),
+                Self::UnknownTag { tag, .. } => format!("unknown tag `{}`", tag),
+                Self::InvalidState { state, states, .. } => {
+                    format!(
+                        "state {} is out of range for a {} state rule",
+                        state, states
+                    )
+                }
+                Self::OutOfBounds {
+                    x,
+                    y,
+                    width,
+                    height,
+                    ..
+                } => format!(
+                    "cell ({}, {}) lies outside the {}x{} pattern",
+                    x, y, width, height
+                ),
+                Self::MissingEnd { .. } => "missing `!` at the end of the pattern".to_owned(),
+                Self::UnknownCell { cell, .. } => format!("unknown cell `{}`", cell),
+                Self::InvalidCoordinates { .. } => "invalid coordinates".to_owned(),
+            }
+        }
+    }
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.position() {
+                Some(Position { line, column }) => {
+                    write!(f, "line {}, column {}: {}", line, column, self.message())
+                }
+                None => write!(f, "{}", self.message()),
+            }
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub enum ParseMode {
+        // anything malformed stops parsing with an error
+        Strict,
+        // malformed input is read the way it most likely meant, with a warning
+        #[default]
+        Lenient,
+    }
+
+    impl ParseMode {
+        // fails in strict mode, otherwise keeps the error as a warning
+        pub(super) fn report(
+            self,
+            error: ParseError,
+            warnings: &mut Vec<ParseError>,
+        ) -> Result<(), ParseError> {
+            match self {
+                Self::Strict => Err(error),
+                Self::Lenient => {
+                    warnings.push(error);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    // #<letter><text> lines before the header
+    fn parse_section(line: &str) -> Option<(char, &str)> {
+        let mut chars = line.strip_prefix('#')?.chars();
+        let letter = chars.next().filter(char::is_ascii_alphabetic)?;
+        Some((letter, chars.as_str()))
+    }
+
+    // parsers below fail with the rest of the line where they stopped
+    fn parse_number(s: &str) -> Result<(u32, &str), &str> {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        Ok((s[..end].parse().map_err(|_| s)?, &s[end..]))
+    }
+
+    // <name> = after an optional comma
+    fn parse_field<'a>(name: &str, rest: &'a str) -> Result<&'a str, &'a str> {
+        let rest = rest.trim_start();
+        let rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+        let value = rest.strip_prefix(name).ok_or(rest)?.trim_start();
+        Ok(value.strip_prefix('=').ok_or(value)?.trim_start())
+    }
+
+    // x = <width>, y = <height>[, rule = <rule>], where the commas and spaces are optional
+    fn parse_header(line: &str) -> Result<(u32, u32, Option<&str>), &str> {
+        let (width, rest) = parse_number(parse_field("x", line)?)?;
+        let (height, rest) = parse_number(parse_field("y", rest)?)?;
+        if rest.trim().is_empty() {
+            return Ok((width, height, None));
+        }
+        let rule = parse_field("rule", rest)?.trim_end();
+        if rule.is_empty() {
+            return Err(rule);
+        }
+        if let Some(end) = rule.find(char::is_whitespace) {
+            return Err(&rule[end..]);
+        }
+        Ok((width, height, Some(rule)))
+    }
+    pub(super) fn is_header(line: &str) -> bool {
+        line.trim_start()
+            .strip_prefix('x')
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    }
+
     pub fn parse_metadata(
         rle: &str,
         name: &str,
         path: &str,
-    ) -> Result<(PatternMetadata, usize), ()> {
+    ) -> Result<(PatternMetadata, usize), ParseError> {
         let mut name = name.to_owned();
         let mut comment = String::new();
         let mut owner = None;
         let mut rule = "23/3".to_owned();
 
+        // lines that are neither sections nor the header are skipped
         let mut start = 0;
-        while let Some(captures) = SECTION_RE.with(|re| re.exec(&rle[start..])) {
-            let letter = captures.get(1).as_string().unwrap();
-            let line = captures.get(2).as_string().unwrap();
-
-            match letter.as_str() {
-                "C" | "c" => {
-                    comment.push_str(line.trim());
-                    comment.push('\n');
-                }
-                "N" => {
-                    name = line.trim().to_owned();
-                }
-                "O" => {
-                    owner = Some(line.trim().to_owned());
+        for line in rle.split_inclusive('\n') {
+            let line_start = start;
+            start += line.len();
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some((letter, line)) = parse_section(line) {
+                match letter {
+                    'C' | 'c' => {
+                        comment.push_str(line.trim());
+                        comment.push('\n');
+                    }
+                    'N' => {
+                        name = line.trim().to_owned();
+                    }
+                    'O' => {
+                        owner = Some(line.trim().to_owned());
+                    }
+                    'r' => {
+                        rule = line.trim().to_owned();
+                    }
+                    _ => {}
                 }
-                "r" => {
-                    rule = line.trim().to_owned();
+            } else if is_header(line) {
+                let (width, height, header_rule) =
+                    parse_header(line).map_err(|rest| ParseError::InvalidHeader {
+                        position: Position::at(rle, line_start + line.len() - rest.len()),
+                    })?;
+                if let Some(header_rule) = header_rule {
+                    rule = header_rule.to_owned();
                 }
-                _ => {}
+                return Ok((
+                    PatternMetadata {
+                        name,
+                        path: path.to_owned(),
+                        comment,
+                        owner,
+                        width,
+                        height,
+                        rule,
+                    },
+                    start,
+                ));
             }
-            start += get_index(&captures) + captures.get(0).as_string().unwrap().len();
         }
-
-        let captures = match HEADER_RE.with(|re| re.exec(&rle[start..])) {
-            Some(c) => c,
-            None => return Err(()),
-        };
-        let width = captures
-            .get(1)
-            .as_string()
-            .unwrap()
-            .as_str()
-            .parse()
-            .unwrap();
-        let height = captures
-            .get(2)
-            .as_string()
-            .unwrap()
-            .as_str()
-            .parse()
-            .unwrap();
-        if let Some(m) = captures.get(3).as_string() {
-            rule = m;
-        }
-        start += get_index(&captures) + captures.get(0).as_string().unwrap().len();
-
-        Ok((
-            PatternMetadata {
-                name,
-                path: path.to_owned(),
-                comment,
-                owner,
-                width,
-                height,
-                rule,
-            },
-            start,
-        ))
+        Err(ParseError::MissingHeader)
     }
 
     pub struct RLEIterator<'a> {
         rle: &'a str,
         i: usize,
         count: usize,
+        state: u8,
         x: i64,
         y: i64,
+        width: u32,
+        height: u32,
+        // of the rule in the header, or as many as a cell can hold if it isn't supported
+        states: u16,
+        mode: ParseMode,
+        finished: bool,
+        out_of_bounds: bool,
+        // problems read past in lenient mode
+        pub warnings: Vec<ParseError>,
+        // what stopped the iterator early in strict mode
+        pub error: Option<ParseError>,
     }
     impl<'a> RLEIterator<'a> {
-        pub fn new(rle: &'a str) -> Result<Self, ()> {
-            let (_, start) = parse_metadata(rle, "Unnamed Pattern", "")?;
+        pub fn new(rle: &'a str) -> Result<Self, ParseError> {
+            Self::with_mode(rle, ParseMode::Lenient)
+        }
+        pub fn with_mode(rle: &'a str, mode: ParseMode) -> Result<Self, ParseError> {
+            let mut warnings = Vec::new();
+            let (width, height, rule, start) = match parse_metadata(rle, "Unnamed Pattern", "") {
+                Ok((metadata, start)) => (metadata.width, metadata.height, metadata.rule, start),
+                // without a header, everything past the sections is the pattern, of any size
+                Err(ParseError::MissingHeader) if mode == ParseMode::Lenient => {
+                    warnings.push(ParseError::MissingHeader);
+                    let start = rle
+                        .split_inclusive('\n')
+                        .take_while(|line| {
+                            let line = line.trim();
+                            line.is_empty() || line.starts_with('#')
+                        })
+                        .map(str::len)
+                        .sum();
+                    (u32::MAX, u32::MAX, "23/3".to_owned(), start)
+                }
+                Err(e) => return Err(e),
+            };
+            let states = parse_rule(&rule).map_or(256, |(rule, _)| rule.states());
 
             Ok(Self {
-                rle: &rle[start..],
-                i: 0,
+                rle,
+                i: start,
                 count: 0,
+                state: 0,
                 x: 0,
                 y: 0,
+                width,
+                height,
+                states,
+                mode,
+                finished: false,
+                out_of_bounds: false,
+                warnings,
+                error: None,
             })
         }
+
+        // whether parsing can go on past the problem
+        fn report(&mut self, error: ParseError) -> bool {
+            match self.mode {
+                ParseMode::Strict => {
+                    self.error = Some(error);
+                    self.finished = true;
+                    false
+                }
+                ParseMode::Lenient => {
+                    self.warnings.push(error);
+                    true
+                }
+            }
+        }
+        fn offset_position(&self, offset: usize) -> Position {
+            Position::at(self.rle, offset)
+        }
     }
     impl<'a> Iterator for RLEIterator<'a> {
-        type Item = (i64, i64);
+        type Item = (i64, i64, u8);
 
         fn next(&mut self) -> Option<Self::Item> {
             if self.count > 0 {
                 self.count -= 1;
                 self.x += 1;
-                return Some((self.x - 1, self.y));
+                return Some((self.x - 1, self.y, self.state));
+            }
+            if self.finished {
+                return None;
             }
 
             let bytes = self.rle.as_bytes();
@@ -139,54 +453,173 @@ pub mod rle {
                     self.i += 1;
                 }
                 if self.i >= bytes.len() {
+                    self.finished = true;
+                    self.report(ParseError::MissingEnd {
+                        position: self.offset_position(self.i),
+                    });
                     return None;
                 }
 
-                let mut count_str_end = self.i;
-                while count_str_end < bytes.len() && bytes[count_str_end].is_ascii_digit() {
-                    count_str_end += 1;
+                let count_start = self.i;
+                while self.i < bytes.len() && bytes[self.i].is_ascii_digit() {
+                    self.i += 1;
                 }
-                self.count = str::from_utf8(&bytes[self.i..count_str_end])
-                    .unwrap()
-                    .parse()
-                    .unwrap_or(1);
-                self.i = count_str_end;
+                let count = &self.rle[count_start..self.i];
+                self.count = match count.parse() {
+                    _ if count.is_empty() => 1,
+                    Ok(count) if count > 0 => count,
+                    _ => {
+                        let error = ParseError::InvalidCount {
+                            position: self.offset_position(count_start),
+                            count: count.to_owned(),
+                        };
+                        if !self.report(error) {
+                            return None;
+                        }
+                        1
+                    }
+                };
                 if self.i >= bytes.len() {
-                    return None;
+                    continue;
                 }
 
-                let tag = bytes[self.i] as char;
-                self.i += 1;
-                match tag {
-                    '!' => return None,
+                let tag_start = self.i;
+                let tag = self.rle[self.i..].chars().next().unwrap();
+                self.i += tag.len_utf8();
+                let state = match tag {
+                    '!' => {
+                        self.finished = true;
+                        return None;
+                    }
                     '$' => {
                         self.y += self.count as i64;
                         self.x = 0;
                         self.count = 0;
+                        continue;
                     }
-                    'b' | 'B' => {
+                    // two-state files may use B for dead cells too
+                    'b' | '.' | 'B' if tag != 'B' || self.states == 2 => {
                         self.x += self.count as i64;
                         self.count = 0;
+                        continue;
+                    }
+                    'o' => 1,
+                    // multi-state: A..X are states 1..24, pA..pX are 25..48, up to yO = 255
+                    'A'..='X' if self.states > 2 => tag as u32 - 'A' as u32 + 1,
+                    'p'..='y'
+                        if self.states > 2 && matches!(bytes.get(self.i), Some(b'A'..=b'X')) =>
+                    {
+                        self.i += 1;
+                        24 * (tag as u32 - 'p' as u32 + 1) + (bytes[self.i - 1] - b'A') as u32 + 1
                     }
                     _ => {
-                        self.count -= 1;
-                        self.x += 1;
-                        return Some((self.x - 1, self.y));
+                        let error = ParseError::UnknownTag {
+                            position: self.offset_position(tag_start),
+                            tag,
+                        };
+                        if !self.report(error) {
+                            return None;
+                        }
+                        1
+                    }
+                };
+                self.state = if state < self.states as u32 {
+                    state as u8
+                } else {
+                    let error = ParseError::InvalidState {
+                        position: self.offset_position(tag_start),
+                        state,
+                        states: self.states,
+                    };
+                    if !self.report(error) {
+                        return None;
+                    }
+                    1
+                };
+
+                let last_x = self.x + self.count as i64 - 1;
+                if !self.out_of_bounds
+                    && (last_x >= self.width as i64 || self.y >= self.height as i64)
+                {
+                    // reported once, a pattern this far off usually has the wrong header
+                    self.out_of_bounds = true;
+                    let error = ParseError::OutOfBounds {
+                        position: self.offset_position(tag_start),
+                        x: last_x.min(self.width as i64).max(self.x),
+                        y: self.y,
+                        width: self.width,
+                        height: self.height,
+                    };
+                    if !self.report(error) {
+                        return None;
                     }
                 }
+                self.count -= 1;
+                self.x += 1;
+                return Some((self.x - 1, self.y, self.state));
             }
         }
     }
-    pub fn iter_alive<'a>(rle: &'a str) -> Result<RLEIterator<'a>, ()> {
+    pub fn iter_alive<'a>(rle: &'a str) -> Result<RLEIterator<'a>, ParseError> {
         RLEIterator::new(rle)
     }
 
-    pub fn to_grid(rle: &str) -> Result<Vec<Vec<u8>>, ()> {
+    pub struct ParsedRle {
+        pub metadata: PatternMetadata,
+        pub cells: Vec<(i64, i64, u8)>,
+        pub warnings: Vec<ParseError>,
+    }
+
+    pub fn parse(rle: &str, mode: ParseMode) -> Result<ParsedRle, ParseError> {
+        let mut iter = RLEIterator::with_mode(rle, mode)?;
+        let cells = iter.by_ref().collect::<Vec<_>>();
+        if let Some(error) = iter.error {
+            return Err(error);
+        }
+        let metadata = match parse_metadata(rle, "Unnamed Pattern", "") {
+            Ok((metadata, _)) => metadata,
+            // read without a header in lenient mode, so the size comes from the cells
+            Err(_) => PatternMetadata {
+                name: "Unnamed Pattern".to_owned(),
+                path: "".to_owned(),
+                comment: "".to_owned(),
+                owner: None,
+                width: cells
+                    .iter()
+                    .map(|&(x, _, _)| x as u32 + 1)
+                    .max()
+                    .unwrap_or(0),
+                height: cells
+                    .iter()
+                    .map(|&(_, y, _)| y as u32 + 1)
+                    .max()
+                    .unwrap_or(0),
+                rule: "23/3".to_owned(),
+            },
+        };
+        Ok(ParsedRle {
+            metadata,
+            cells,
+            warnings: iter.warnings,
+        })
+    }
+
+    // cells past the declared size grow the grid to fit
+    pub fn to_grid(rle: &str) -> Result<Vec<Vec<u8>>, ParseError> {
         let (PatternMetadata { width, height, .. }, _) =
             parse_metadata(rle, "Unnamed Pattern", "")?;
-        let mut rect = vec![vec![0; width as usize]; height as usize];
-        for (x, y) in iter_alive(rle)? {
-            rect[y as usize][x as usize] = 1;
+        let cells = iter_alive(rle)?.collect::<Vec<_>>();
+        let width = cells
+            .iter()
+            .map(|&(x, _, _)| x as usize + 1)
+            .fold(width as usize, usize::max);
+        let height = cells
+            .iter()
+            .map(|&(_, y, _)| y as usize + 1)
+            .fold(height as usize, usize::max);
+        let mut rect = vec![vec![0; width]; height];
+        for (x, y, state) in cells {
+            rect[y as usize][x as usize] = state;
         }
         Ok(rect)
     }
@@ -200,48 +633,68 @@ pub mod rle {
 
         count_str + value
     }
-    pub fn from_iter(iter: UniverseIterator, x1: i64, y1: i64, x2: i64, y2: i64) -> String {
+    fn state_tag(state: u8, rule: &Rule) -> String {
+        if rule.states() <= 2 {
+            "o".to_owned()
+        } else if state <= 24 {
+            char::from(b'A' + state - 1).to_string()
+        } else {
+            let (prefix, letter) = ((state - 25) / 24, (state - 25) % 24);
+            format!("{}{}", char::from(b'p' + prefix), char::from(b'A' + letter))
+        }
+    }
+    pub fn from_iter(
+        iter: UniverseIterator,
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+        rule: &Rule,
+        grid: Option<&BoundedGrid>,
+    ) -> String {
         let mut cells = iter.collect::<Vec<_>>();
-        cells.sort_by_key(|&(x, y)| (y, x));
+        cells.sort_by_key(|&(x, y, _)| (y, x));
         let (width, height) = (x2 - x1 + 1, y2 - y1 + 1);
+        let dead = if rule.states() > 2 { "." } else { "b" };
 
         let mut items = Vec::new();
         let (mut px, mut py) = (-1, 0);
-        let mut run = 0;
-        for (mut x, mut y) in cells {
+        let (mut run, mut run_state) = (0, 0);
+        for (mut x, mut y, state) in cells {
             x -= x1;
             y -= y1;
             let dy = y - py;
+            if run > 0 && (dy > 0 || x - px > 1 || state != run_state) {
+                items.push(item(run, &state_tag(run_state, rule)));
+                run = 0;
+            }
             if dy > 0 {
-                if run > 0 {
-                    items.push(item(run, "o"));
-                    run = 0;
-                }
                 items.push(item(dy, "$"));
                 px = -1;
             }
             let dx = x - px - 1;
             if dx > 0 {
-                if run > 0 {
-                    items.push(item(run, "o"));
-                    run = 0;
-                }
-                items.push(item(dx, "b"));
+                items.push(item(dx, dead));
             }
             run += 1;
+            run_state = state;
 
             px = x;
             py = y;
         }
         if run > 0 {
-            items.push(item(run, "o"));
+            items.push(item(run, &state_tag(run_state, rule)));
         }
-        while !items.is_empty() && items.last().unwrap().ends_with('b') {
+        while !items.is_empty() && items.last().unwrap().ends_with(dead) {
             items.pop();
         }
         items.push("!".to_owned());
 
-        let mut rle = format!("x = {}, y = {}\n", width, height);
+        let mut rle = format!("x = {}, y = {}, rule = {}", width, height, rule);
+        if let Some(grid) = grid {
+            rle.push_str(&format!(":{}", grid));
+        }
+        rle.push('\n');
         let mut line_len = 0;
         for item in items.iter() {
             line_len += item.len();
@@ -254,3 +707,1018 @@ pub mod rle {
         rle
     }
 }
+pub mod plaintext {
+    use super::rle::{ParseError, ParseMode, ParsedRle, PatternMetadata, Position};
+    use crate::universe::UniverseIterator;
+
+    pub fn read(text: &str, mode: ParseMode) -> Result<ParsedRle, ParseError> {
+        let mut name = "Unnamed Pattern".to_owned();
+        let mut comment = String::new();
+        let mut cells = Vec::new();
+        let mut warnings = Vec::new();
+        let (mut width, mut height, mut y) = (0, 0, 0);
+
+        let mut start = 0;
+        for line in text.split_inclusive('\n') {
+            let line_start = start;
+            start += line.len();
+            let line = line.trim_end();
+            if let Some(line) = line.strip_prefix('!') {
+                if let Some(line) = line.strip_prefix("Name:") {
+                    name = line.trim().to_owned();
+                } else {
+                    comment.push_str(line.trim());
+                    comment.push('\n');
+                }
+                continue;
+            }
+            for (x, (i, c)) in line.char_indices().enumerate() {
+                match c {
+                    '.' => {}
+                    'O' | '*' => cells.push((x as i64, y, 1)),
+                    _ => {
+                        let error = ParseError::UnknownCell {
+                            position: Position::at(text, line_start + i),
+                            cell: c,
+                        };
+                        mode.report(error, &mut warnings)?;
+                        cells.push((x as i64, y, 1));
+                    }
+                }
+            }
+            y += 1;
+            if !line.is_empty() {
+                width = width.max(line.chars().count() as u32);
+                height = y as u32;
+            }
+        }
+
+        Ok(ParsedRle {
+            metadata: PatternMetadata {
+                name,
+                path: "".to_owned(),
+                comment,
+                owner: None,
+                width,
+                height,
+                rule: "23/3".to_owned(),
+            },
+            cells,
+            warnings,
+        })
+    }
+
+    // every live state is written as O, the format has no others
+    pub fn write(iter: UniverseIterator, x1: i64, y1: i64, name: &str) -> String {
+        let mut cells = iter.collect::<Vec<_>>();
+        cells.sort_by_key(|&(x, y, _)| (y, x));
+
+        let mut text = format!("!Name: {}\n", name);
+        let mut row = String::new();
+        let mut py = y1;
+        for (x, y, _) in cells {
+            while py < y {
+                text.push_str(&row);
+                text.push('\n');
+                row.clear();
+                py += 1;
+            }
+            row.push_str(&".".repeat((x - x1) as usize - row.len()));
+            row.push('O');
+        }
+        text.push_str(&row);
+        text.push('\n');
+        text
+    }
+}
+pub mod life {
+    use super::rle::{ParseError, ParseMode, ParsedRle, PatternMetadata, Position};
+    use crate::{rule::Rule, universe::UniverseIterator};
+
+    // moves the cells so the pattern starts at (0, 0), like an RLE would
+    fn parsed(
+        mut cells: Vec<(i64, i64, u8)>,
+        rule: String,
+        comment: String,
+        warnings: Vec<ParseError>,
+    ) -> ParsedRle {
+        let x1 = cells.iter().map(|&(x, _, _)| x).min().unwrap_or(0);
+        let y1 = cells.iter().map(|&(_, y, _)| y).min().unwrap_or(0);
+        let x2 = cells.iter().map(|&(x, _, _)| x).max().unwrap_or(-1);
+        let y2 = cells.iter().map(|&(_, y, _)| y).max().unwrap_or(-1);
+        for (x, y, _) in cells.iter_mut() {
+            *x -= x1;
+            *y -= y1;
+        }
+        ParsedRle {
+            metadata: PatternMetadata {
+                name: "Unnamed Pattern".to_owned(),
+                path: "".to_owned(),
+                comment,
+                owner: None,
+                width: (x2 - x1 + 1) as u32,
+                height: (y2 - y1 + 1) as u32,
+                rule,
+            },
+            cells,
+            warnings,
+        }
+    }
+
+    fn parse_coordinates(s: &str) -> Option<(i64, i64)> {
+        let mut parts = s.split_whitespace();
+        let coordinates = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+        parts.next().is_none().then_some(coordinates)
+    }
+
+    // #Life 1.05: #D description, #N or #R rule, then #P x y blocks of . and *
+    pub fn read_105(text: &str, mode: ParseMode) -> Result<ParsedRle, ParseError> {
+        let mut rule = "23/3".to_owned();
+        let mut comment = String::new();
+        let mut cells = Vec::new();
+        let mut warnings = Vec::new();
+        let (mut bx, mut by, mut y) = (0, 0, 0);
+
+        let mut start = 0;
+        for line in text.split_inclusive('\n') {
+            let line_start = start;
+            start += line.len();
+            let line = line.trim_end();
+            if let Some(line) = line.strip_prefix("#P") {
+                match parse_coordinates(line) {
+                    Some((x, y1)) => (bx, by, y) = (x, y1, 0),
+                    None => {
+                        let error = ParseError::InvalidCoordinates {
+                            position: Position::at(text, line_start + 2),
+                        };
+                        mode.report(error, &mut warnings)?;
+                    }
+                }
+                continue;
+            }
+            if let Some(line) = line.strip_prefix("#D") {
+                comment.push_str(line.trim());
+                comment.push('\n');
+                continue;
+            }
+            if line.starts_with("#N") {
+                rule = "23/3".to_owned();
+                continue;
+            }
+            if let Some(line) = line.strip_prefix("#R") {
+                rule = line.trim().to_owned();
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            for (x, (i, c)) in line.char_indices().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' | 'O' => cells.push((bx + x as i64, by + y, 1)),
+                    _ => {
+                        let error = ParseError::UnknownCell {
+                            position: Position::at(text, line_start + i),
+                            cell: c,
+                        };
+                        mode.report(error, &mut warnings)?;
+                        cells.push((bx + x as i64, by + y, 1));
+                    }
+                }
+            }
+            y += 1;
+        }
+        Ok(parsed(cells, rule, comment, warnings))
+    }
+
+    // #Life 1.06: one x y pair per live cell
+    pub fn read_106(text: &str, mode: ParseMode) -> Result<ParsedRle, ParseError> {
+        let mut cells = Vec::new();
+        let mut warnings = Vec::new();
+
+        let mut start = 0;
+        for line in text.split_inclusive('\n') {
+            let line_start = start;
+            start += line.len();
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_coordinates(line) {
+                Some((x, y)) => cells.push((x, y, 1)),
+                None => {
+                    let error = ParseError::InvalidCoordinates {
+                        position: Position::at(text, line_start),
+                    };
+                    mode.report(error, &mut warnings)?;
+                }
+            }
+        }
+        Ok(parsed(cells, "23/3".to_owned(), "".to_owned(), warnings))
+    }
+
+    pub fn write_105(
+        iter: UniverseIterator,
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+        rule: &Rule,
+    ) -> String {
+        let mut rows = vec![vec![b'.'; (x2 - x1 + 1) as usize]; (y2 - y1 + 1) as usize];
+        for (x, y, _) in iter {
+            rows[(y - y1) as usize][(x - x1) as usize] = b'*';
+        }
+
+        let mut text = "#Life 1.05\n".to_owned();
+        if *rule == Rule::LIFE {
+            text.push_str("#N\n");
+        } else {
+            text.push_str(&format!("#R {}\n", rule));
+        }
+        text.push_str(&format!("#P {} {}\n", x1, y1));
+        for row in rows {
+            let row = String::from_utf8(row).unwrap();
+            text.push_str(row.trim_end_matches('.'));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn write_106(iter: UniverseIterator) -> String {
+        let mut text = "#Life 1.06\n".to_owned();
+        for (x, y, _) in iter {
+            text.push_str(&format!("{} {}\n", x, y));
+        }
+        text
+    }
+}
+// catagolue's object names: a prefix such as xs4 or xp2, then the cells in extended wechsler format
+pub mod apgcode {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    type Orientation = fn(i64, i64) -> (i64, i64);
+
+    const ORIENTATIONS: [Orientation; 8] = [
+        |x, y| (x, y),
+        |x, y| (-x, y),
+        |x, y| (x, -y),
+        |x, y| (-x, -y),
+        |x, y| (y, x),
+        |x, y| (-y, x),
+        |x, y| (y, -x),
+        |x, y| (-y, -x),
+    ];
+
+    // empty columns: 0, w and x for one to three, y0 to yz for four to 39
+    fn push_zeros(code: &mut String, mut zeros: usize) {
+        while zeros > 39 {
+            code.push_str("yz");
+            zeros -= 39;
+        }
+        match zeros {
+            0 => {}
+            1 => code.push('0'),
+            2 => code.push('w'),
+            3 => code.push('x'),
+            _ => {
+                code.push('y');
+                code.push(DIGITS[zeros - 4] as char);
+            }
+        }
+    }
+
+    // columns of 5-row strips as base 32 digits, top row lowest, strips separated by z
+    pub fn encode(cells: impl Iterator<Item = (i64, i64)>) -> String {
+        let cells = cells.collect::<Vec<_>>();
+        if cells.is_empty() {
+            return "0".to_owned();
+        }
+        let (x1, y1, x2, y2) = cells.iter().fold(
+            (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+            |(x1, y1, x2, y2), &(x, y)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+        );
+        let mut strips = vec![vec![0; (x2 - x1 + 1) as usize]; ((y2 - y1) / 5 + 1) as usize];
+        for (x, y) in cells {
+            strips[((y - y1) / 5) as usize][(x - x1) as usize] |= 1 << ((y - y1) % 5);
+        }
+
+        let mut code = String::new();
+        for (i, strip) in strips.iter().enumerate() {
+            if i > 0 {
+                code.push('z');
+            }
+            // trailing empty columns are left out
+            let mut zeros = 0;
+            for &column in strip {
+                if column == 0 {
+                    zeros += 1;
+                    continue;
+                }
+                push_zeros(&mut code, zeros);
+                zeros = 0;
+                code.push(DIGITS[column] as char);
+            }
+        }
+        code
+    }
+
+    // the shortest encoding over every phase and orientation, ties going to the first in ascii
+    pub fn canonical(phases: &[Vec<(i64, i64)>]) -> String {
+        phases
+            .iter()
+            .flat_map(|cells| {
+                ORIENTATIONS
+                    .map(|orientation| encode(cells.iter().map(|&(x, y)| orientation(x, y))))
+            })
+            .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+            .unwrap_or_else(|| "0".to_owned())
+    }
+
+    // xs, xp and xq codes, the only ones made of cells
+    pub fn decode(apgcode: &str) -> Option<Vec<(i64, i64, u8)>> {
+        let (prefix, body) = apgcode.trim().split_once('_')?;
+        let number = prefix
+            .strip_prefix("xs")
+            .or_else(|| prefix.strip_prefix("xp"))
+            .or_else(|| prefix.strip_prefix("xq"))?;
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) || body.is_empty() {
+            return None;
+        }
+        let digit = |c: char| DIGITS.iter().position(|&d| d as char == c);
+
+        let mut cells = Vec::new();
+        let (mut x, mut y) = (0, 0);
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                'z' => (x, y) = (0, y + 5),
+                'w' => x += 2,
+                'x' => x += 3,
+                'y' => x += 4 + digit(chars.next()?)? as i64,
+                _ => {
+                    let column = digit(c).filter(|&column| column < 32)?;
+                    for bit in 0..5 {
+                        if column & (1 << bit) != 0 {
+                            cells.push((x, y + bit, 1));
+                        }
+                    }
+                    x += 1;
+                }
+            }
+        }
+        Some(cells)
+    }
+}
+pub mod macrocell {
+    use crate::{
+        quadtree::{Branch, LEAF_LEVEL, LEAF_SIZE, Leaf, Node, NodeKind, NodeRef},
+        universe::{MAX_LEVEL, MIN_LEVEL, Universe},
+    };
+    use rustc_hash::FxHashMap;
+
+    // two-state files store 8x8 bitmaps at this level instead of nodes
+    const BITMAP_LEVEL: u8 = LEAF_LEVEL + 1;
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct MacrocellMetadata {
+        pub rule: String,
+        pub generation: u64,
+        pub comment: String,
+    }
+
+    #[derive(Clone, Copy)]
+    enum Entry {
+        // level 1 nodes of multi-state files, the states of [nw, ne, sw, se]
+        Cells([u8; 4]),
+        Node(NodeRef),
+    }
+
+    fn parse_bitmap(line: &str, universe: &mut Universe) -> Option<NodeRef> {
+        let mut leaves = [Leaf::default(); 4];
+        let (mut x, mut y) = (0, 0);
+        for c in line.chars() {
+            match c {
+                '.' => x += 1,
+                '*' => {
+                    if x >= 2 * LEAF_SIZE || y >= 2 * LEAF_SIZE {
+                        return None;
+                    }
+                    let i = (y / LEAF_SIZE) * 2 + x / LEAF_SIZE;
+                    leaves[i].set(x % LEAF_SIZE, y % LEAF_SIZE, 1);
+                    x += 1;
+                }
+                '$' => (x, y) = (0, y + 1),
+                _ => return None,
+            }
+        }
+        let mut children = Branch::default();
+        for (child, leaf) in children.iter_mut().zip(leaves) {
+            *child = universe
+                .arena
+                .insert(Node::new_leaf(leaf, leaf.population()));
+        }
+        let population = leaves.iter().map(Leaf::population).sum();
+        Some(
+            universe
+                .arena
+                .insert(Node::new_branch(children, BITMAP_LEVEL, population)),
+        )
+    }
+
+    fn parse_node(
+        line: &str,
+        entries: &[(u8, Entry)],
+        universe: &mut Universe,
+    ) -> Option<(u8, Entry)> {
+        let fields = line
+            .split_ascii_whitespace()
+            .map(|f| f.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let &[level, nw, ne, sw, se] = fields.as_slice() else {
+            return None;
+        };
+        let level = u8::try_from(level)
+            .ok()
+            .filter(|l| (1..=MAX_LEVEL).contains(l))?;
+        if level == 1 {
+            let mut cells = [0; 4];
+            for (cell, state) in cells.iter_mut().zip([nw, ne, sw, se]) {
+                *cell = u8::try_from(state).ok()?;
+            }
+            return Some((1, Entry::Cells(cells)));
+        }
+        let mut children = [Entry::Cells([0; 4]); 4];
+        for (child, index) in children.iter_mut().zip([nw, ne, sw, se]) {
+            *child = match index as usize {
+                // 0 is the empty node of any level
+                0 if level - 1 == 1 => Entry::Cells([0; 4]),
+                0 => Entry::Node(universe.empty_ref[(level - 1) as usize]),
+                i => match entries.get(i - 1) {
+                    Some(&(child_level, entry)) if child_level == level - 1 => entry,
+                    _ => return None,
+                },
+            };
+        }
+
+        let node = if level == LEAF_LEVEL {
+            let mut leaf = Leaf::default();
+            for (i, child) in children.iter().enumerate() {
+                let Entry::Cells(cells) = child else {
+                    return None;
+                };
+                for (j, &state) in cells.iter().enumerate() {
+                    leaf.set((i % 2) * 2 + j % 2, (i / 2) * 2 + j / 2, state);
+                }
+            }
+            Node::new_leaf(leaf, leaf.population())
+        } else {
+            let mut branch = Branch::default();
+            let mut population = 0;
+            for (child, entry) in branch.iter_mut().zip(children) {
+                let Entry::Node(node_ref) = entry else {
+                    return None;
+                };
+                *child = node_ref;
+                population += universe.arena.get(node_ref).population;
+            }
+            Node::new_branch(branch, level, population)
+        };
+        Some((level, Entry::Node(universe.arena.insert(node))))
+    }
+
+    // the root of a file is centered on the origin like ours,
+    // so small roots only need growing to the minimum level
+    fn centered(level: u8, entry: Entry, universe: &mut Universe) -> NodeRef {
+        let mut node_ref = match entry {
+            Entry::Cells(cells) => {
+                let mut leaf = Leaf::default();
+                for (j, &state) in cells.iter().enumerate() {
+                    leaf.set(j % 2 + 1, j / 2 + 1, state);
+                }
+                universe
+                    .arena
+                    .insert(Node::new_leaf(leaf, leaf.population()))
+            }
+            Entry::Node(node_ref) => node_ref,
+        };
+        if level <= LEAF_LEVEL {
+            let leaf = *universe.arena.get(node_ref).data.as_leaf();
+            let mut children = Branch::default();
+            for (i, child) in children.iter_mut().enumerate() {
+                let mut quadrant = Leaf::default();
+                let (ox, oy) = ((i % 2) * 2, (i / 2) * 2);
+                for y in oy..oy + 2 {
+                    for x in ox..ox + 2 {
+                        quadrant.set(x + 2 - 2 * ox, y + 2 - 2 * oy, leaf.get(x, y));
+                    }
+                }
+                *child = universe
+                    .arena
+                    .insert(Node::new_leaf(quadrant, quadrant.population()));
+            }
+            node_ref = universe.arena.insert(Node::new_branch(
+                children,
+                LEAF_LEVEL + 1,
+                leaf.population(),
+            ));
+        }
+        while universe.arena.get(node_ref).level < MIN_LEVEL {
+            node_ref = universe.grown(node_ref);
+        }
+        node_ref
+    }
+
+    // builds the nodes of an [M2] file in the universe's arena, returning its root.
+    // the universe itself is left untouched
+    pub fn read(mc: &str, universe: &mut Universe) -> Option<(MacrocellMetadata, NodeRef)> {
+        let mut lines = mc.lines();
+        if !lines.next()?.trim_start().starts_with("[M2]") {
+            return None;
+        }
+        let mut metadata = MacrocellMetadata {
+            rule: "B3/S23".to_owned(),
+            generation: 0,
+            comment: String::new(),
+        };
+        let mut entries = Vec::new();
+        for line in lines {
+            let line = line.trim();
+            if let Some(section) = line.strip_prefix('#') {
+                let (letter, value) = section.split_at(section.len().min(1));
+                match letter {
+                    "R" => metadata.rule = value.trim().to_owned(),
+                    "G" => metadata.generation = value.trim().parse().ok()?,
+                    "C" | "c" | "D" => {
+                        metadata.comment.push_str(value.trim());
+                        metadata.comment.push('\n');
+                    }
+                    _ => {}
+                }
+            } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+                let entry = parse_node(line, &entries, universe)?;
+                entries.push(entry);
+            } else if !line.is_empty() {
+                let entry = Entry::Node(parse_bitmap(line, universe)?);
+                entries.push((BITMAP_LEVEL, entry));
+            }
+        }
+        // the last node is the root
+        let root = match entries.last() {
+            Some(&(level, entry)) => centered(level, entry, universe),
+            None => universe.empty_ref[MIN_LEVEL as usize],
+        };
+        Some((metadata, root))
+    }
+
+    struct Writer<'a> {
+        universe: &'a Universe,
+        multistate: bool,
+        lines: Vec<String>,
+        indices: FxHashMap<NodeRef, usize>,
+        cells: FxHashMap<[u8; 4], usize>,
+    }
+
+    impl Writer<'_> {
+        fn push(&mut self, line: String) -> usize {
+            self.lines.push(line);
+            self.lines.len()
+        }
+        fn bitmap(&self, children: &Branch) -> String {
+            let mut line = String::new();
+            for y in 0..2 * LEAF_SIZE {
+                for x in 0..2 * LEAF_SIZE {
+                    let leaf = self
+                        .universe
+                        .arena
+                        .get(children[(y / LEAF_SIZE) * 2 + x / LEAF_SIZE]);
+                    let alive = leaf.data.as_leaf().get(x % LEAF_SIZE, y % LEAF_SIZE) != 0;
+                    line.push(if alive { '*' } else { '.' });
+                }
+                while line.ends_with('.') {
+                    line.pop();
+                }
+                line.push('$');
+            }
+            while line.ends_with("$$") {
+                line.pop();
+            }
+            line
+        }
+        fn visit_cells(&mut self, cells: [u8; 4]) -> usize {
+            if cells == [0; 4] {
+                return 0;
+            }
+            if let Some(&i) = self.cells.get(&cells) {
+                return i;
+            }
+            let [nw, ne, sw, se] = cells;
+            let i = self.push(format!("1 {} {} {} {}", nw, ne, sw, se));
+            self.cells.insert(cells, i);
+            i
+        }
+        fn visit(&mut self, node_ref: NodeRef) -> usize {
+            let node = self.universe.arena.get(node_ref);
+            if node.population == 0 {
+                return 0;
+            }
+            if let Some(&i) = self.indices.get(&node_ref) {
+                return i;
+            }
+            let line = match node.data {
+                NodeKind::Branch(children) if !self.multistate && node.level == BITMAP_LEVEL => {
+                    self.bitmap(&children)
+                }
+                NodeKind::Branch(children) => {
+                    let [nw, ne, sw, se] = children.map(|child| self.visit(child));
+                    format!("{} {} {} {} {}", node.level, nw, ne, sw, se)
+                }
+                NodeKind::Leaf(leaf) => {
+                    let [nw, ne, sw, se] = [0, 1, 2, 3].map(|i| {
+                        let (ox, oy) = ((i % 2) * 2, (i / 2) * 2);
+                        self.visit_cells([
+                            leaf.get(ox, oy),
+                            leaf.get(ox + 1, oy),
+                            leaf.get(ox, oy + 1),
+                            leaf.get(ox + 1, oy + 1),
+                        ])
+                    });
+                    format!("{} {} {} {} {}", LEAF_LEVEL, nw, ne, sw, se)
+                }
+            };
+            let i = self.push(line);
+            self.indices.insert(node_ref, i);
+            i
+        }
+    }
+
+    pub fn write(universe: &Universe) -> String {
+        let mut writer = Writer {
+            universe,
+            multistate: universe.rule.states() > 2,
+            lines: Vec::new(),
+            indices: FxHashMap::default(),
+            cells: FxHashMap::default(),
+        };
+        if writer.visit(universe.root()) == 0 {
+            writer.push(format!("{} 0 0 0 0", universe.level()));
+        }
+
+        let mut mc = "[M2] (rust-wasm-gol)\n".to_owned();
+        mc.push_str(&format!("#R {}", universe.rule));
+        if let Some(grid) = &universe.grid {
+            mc.push_str(&format!(":{}", grid));
+        }
+        mc.push('\n');
+        mc.push_str(&format!("#G {}\n", universe.generation));
+        for line in writer.lines {
+            mc.push_str(&line);
+            mc.push('\n');
+        }
+        mc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quadtree::LEAF_LEVEL, universe::MAX_LEVEL};
+
+    #[test]
+    fn two_state_dead_b() {
+        let text = "x = 5, y = 1, rule = B3/S23\n3B2o!";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let parsed = parse(text, mode).unwrap();
+            assert_eq!(parsed.cells, vec![(3, 0, 1), (4, 0, 1)]);
+            assert!(parsed.warnings.is_empty());
+        }
+    }
+
+    // the strict error as it's shown, then the cells read past it in lenient mode
+    fn check_error(text: &str, message: &str, cells: &[(i64, i64, u8)]) {
+        let error = parse(text, ParseMode::Strict).err().unwrap();
+        assert_eq!(error.to_string(), message);
+        let parsed = parse(text, ParseMode::Lenient).unwrap();
+        assert_eq!(parsed.warnings, vec![error]);
+        assert_eq!(parsed.cells, cells);
+    }
+
+    #[test]
+    fn unknown_tag() {
+        check_error(
+            "x = 3, y = 1\noxo!",
+            "line 2, column 2: unknown tag `x`",
+            &[(0, 0, 1), (1, 0, 1), (2, 0, 1)],
+        );
+    }
+
+    #[test]
+    fn invalid_count() {
+        check_error(
+            "x = 3, y = 2\no$\n0o2o!",
+            "line 3, column 1: invalid run count `0`",
+            &[(0, 0, 1), (0, 1, 1), (1, 1, 1), (2, 1, 1)],
+        );
+    }
+
+    #[test]
+    fn missing_header() {
+        let text = "#N Block\n2o$2o!";
+        check_error(
+            text,
+            "missing header, expected `x = <width>, y = <height>`",
+            &[(0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)],
+        );
+        let parsed = parse(text, ParseMode::Lenient).unwrap();
+        assert_eq!((parsed.metadata.width, parsed.metadata.height), (2, 2));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        check_error(
+            "x = 2, y = 1\n  3o!",
+            "line 2, column 4: cell (2, 0) lies outside the 2x1 pattern",
+            &[(0, 0, 1), (1, 0, 1), (2, 0, 1)],
+        );
+    }
+
+    #[test]
+    fn invalid_state() {
+        check_error(
+            "x = 2, y = 1, rule = 23/3/3\nBC!",
+            "line 2, column 2: state 3 is out of range for a 3 state rule",
+            &[(0, 0, 2), (1, 0, 1)],
+        );
+        check_error(
+            "x = 1, y = 1, rule = 23/3/3\nyP!",
+            "line 2, column 1: state 256 is out of range for a 3 state rule",
+            &[(0, 0, 1)],
+        );
+    }
+
+    #[test]
+    fn sections_and_header() {
+        let text = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship.\n#c www.conwaylife.com\n\
+                    #r 23/36\nx=3,y=3\nbo$2bo$3o!";
+        let (metadata, _) = rle::parse_metadata(text, "Unnamed Pattern", "").unwrap();
+        assert_eq!(metadata.name, "Glider");
+        assert_eq!(metadata.owner.as_deref(), Some("Richard K. Guy"));
+        assert_eq!(
+            metadata.comment,
+            "The smallest spaceship.\nwww.conwaylife.com\n"
+        );
+        assert_eq!((metadata.width, metadata.height), (3, 3));
+        assert_eq!(metadata.rule, "23/36");
+
+        // the rule in the header wins over #r
+        let text = "#r 23/36\nx = 3, y = 3, rule = B3/S23:T3,3\nbo$2bo$3o!";
+        let (metadata, _) = rle::parse_metadata(text, "Unnamed Pattern", "").unwrap();
+        assert_eq!(metadata.rule, "B3/S23:T3,3");
+
+        let error = rle::parse_metadata("x = 3, y = 3, rule = B3 S23\n", "", "").unwrap_err();
+        assert_eq!(
+            error.position(),
+            Some(rle::Position {
+                line: 1,
+                column: 24
+            })
+        );
+    }
+
+    #[test]
+    fn detect() {
+        let formats = [
+            ("x = 3, y = 3\nbo$2bo$3o!", Format::Rle),
+            ("#C comment\nx = 3, y = 3\nbo$2bo$3o!", Format::Rle),
+            (
+                "[M2] (golly 4.0)\n#R B3/S23\n$$$$$$*$.*$",
+                Format::Macrocell,
+            ),
+            ("!Name: Glider\n.O\n..O\nOOO", Format::Plaintext),
+            (".O\n..O\nOOO", Format::Plaintext),
+            ("#Life 1.05\n#P 0 0\n.*\n..*\n***", Format::Life105),
+            ("#Life 1.06\n1 0\n2 1\n0 2\n1 2\n2 2", Format::Life106),
+            ("xq4_153", Format::Apgcode),
+            ("xs4_33", Format::Apgcode),
+        ];
+        for (text, format) in formats {
+            assert_eq!(Format::detect(text), format, "{}", text);
+        }
+    }
+
+    fn universe_with(cells: &[(i64, i64, u8)]) -> Universe {
+        let mut u = Universe::default();
+        for &(x, y, state) in cells {
+            u.set(x, y, state);
+        }
+        u
+    }
+
+    #[test]
+    fn plaintext_round_trip() {
+        let cells = [
+            (1, -1, 1),
+            (2, 0, 1),
+            (0, 1, 1),
+            (1, 1, 1),
+            (2, 1, 1),
+            (5, 3, 1),
+        ];
+        let u = universe_with(&cells);
+        let text = plaintext::write(u.iter_alive(), 0, -1, "Glider");
+        assert!(text.starts_with("!Name: Glider\n"));
+
+        let parsed = parse(&text, ParseMode::Strict).unwrap();
+        assert_eq!(parsed.metadata.name, "Glider");
+        let mut read = parsed.cells.clone();
+        read.sort_by_key(|&(x, y, _)| (y, x));
+        let moved = cells.map(|(x, y, state)| (x, y + 1, state));
+        assert_eq!(read, moved);
+    }
+
+    #[test]
+    fn life_105_blocks() {
+        let text = "#Life 1.05\n#D two blocks\n#R 23/36\n#P -1 -1\n**\n**\n#P 3 2\n.*\n*.*\n";
+        let parsed = parse(text, ParseMode::Strict).unwrap();
+        assert_eq!(parsed.metadata.rule, "23/36");
+        assert_eq!(parsed.metadata.comment, "two blocks\n");
+        assert_eq!((parsed.metadata.width, parsed.metadata.height), (7, 5));
+        // moved so the pattern starts at (0, 0)
+        assert_eq!(
+            parsed.cells,
+            vec![
+                (0, 0, 1),
+                (1, 0, 1),
+                (0, 1, 1),
+                (1, 1, 1),
+                (5, 3, 1),
+                (4, 4, 1),
+                (6, 4, 1),
+            ]
+        );
+
+        let u = universe_with(&parsed.cells);
+        let text = life::write_105(u.iter_alive(), 0, 0, 6, 4, &"23/36".parse().unwrap());
+        let read = parse(&text, ParseMode::Strict).unwrap();
+        assert_eq!(read.metadata.rule, "B36/S23");
+        assert_eq!(read.cells, parsed.cells);
+    }
+
+    #[test]
+    fn life_105_normal_rule() {
+        // #N means normal Life, even after another rule
+        let text = "#Life 1.05\n#R 23/36\n#N\n#P 0 0\n*\n";
+        let parsed = parse(text, ParseMode::Strict).unwrap();
+        assert_eq!(parsed.metadata.rule, "23/3");
+    }
+
+    #[test]
+    fn life_106() {
+        let text = "#Life 1.06\n-1 -1\n0 -1\n 3 2 \n";
+        let parsed = parse(text, ParseMode::Strict).unwrap();
+        assert_eq!(parsed.cells, vec![(0, 0, 1), (1, 0, 1), (4, 3, 1)]);
+    }
+
+    // every phase of a pattern over its period
+    fn phases(code: &str, period: usize) -> Vec<Vec<(i64, i64)>> {
+        let mut u = universe_with(&apgcode::decode(code).unwrap());
+        (0..period)
+            .map(|_| {
+                let cells = u.iter_alive().map(|(x, y, _)| (x, y)).collect();
+                u.step();
+                cells
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apgcode_known() {
+        for (code, period) in [("xq4_153", 4), ("xs4_33", 1), ("xp2_7", 2), ("xs6_696", 1)] {
+            let (_, body) = code.split_once('_').unwrap();
+            assert_eq!(apgcode::canonical(&phases(code, period)), body);
+        }
+        // column by column, top row first
+        assert_eq!(
+            apgcode::decode("xq4_153").unwrap(),
+            vec![(0, 0, 1), (1, 0, 1), (1, 2, 1), (2, 0, 1), (2, 1, 1)]
+        );
+        assert_eq!(
+            apgcode::decode("xs4_3z3"),
+            Some(vec![(0, 0, 1), (0, 1, 1), (0, 5, 1), (0, 6, 1)])
+        );
+        assert_eq!(apgcode::decode("xs4_"), None);
+        assert_eq!(apgcode::decode("xs_33"), None);
+    }
+
+    #[test]
+    fn apgcode_round_trip() {
+        for body in ["153", "33", "7", "696", "g8o653z11", "wgw1z11", "y11z11"] {
+            let cells = apgcode::decode(&format!("xs0_{}", body)).unwrap();
+            assert_eq!(
+                apgcode::encode(cells.into_iter().map(|(x, y, _)| (x, y))),
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn apgcode_symmetries() {
+        let orientations: [fn(i64, i64) -> (i64, i64); 8] = [
+            |x, y| (x, y),
+            |x, y| (-x, y),
+            |x, y| (x, -y),
+            |x, y| (-x, -y),
+            |x, y| (y, x),
+            |x, y| (-y, x),
+            |x, y| (y, -x),
+            |x, y| (-y, -x),
+        ];
+        for (code, period) in [
+            ("xq4_153", 4),
+            ("xp2_7", 2),
+            ("xp3_co9nas0san9oczgoldlo0oldlogz1047210127401", 3),
+        ] {
+            let phases = phases(code, period);
+            let (_, body) = code.split_once('_').unwrap();
+            for orientation in orientations {
+                for start in 0..period {
+                    let moved = (0..period)
+                        .map(|t| {
+                            phases[(start + t) % period]
+                                .iter()
+                                .map(|&(x, y)| {
+                                    let (x, y) = orientation(x, y);
+                                    (x + 7, y - 3)
+                                })
+                                .collect()
+                        })
+                        .collect::<Vec<_>>();
+                    assert_eq!(apgcode::canonical(&moved), body);
+                }
+            }
+        }
+    }
+
+    fn sorted_cells(u: &Universe) -> Vec<(i64, i64, u8)> {
+        let mut cells = u.iter_alive().collect::<Vec<_>>();
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn macrocell_round_trip() {
+        for (rule, cells) in [
+            (
+                "B3/S23",
+                vec![
+                    (1, 0, 1),
+                    (2, 1, 1),
+                    (0, 2, 1),
+                    (1, 2, 1),
+                    (2, 2, 1),
+                    (-40, 30, 1),
+                ],
+            ),
+            (
+                "23/34/4",
+                vec![(0, 0, 1), (1, 0, 2), (0, 1, 3), (5, -9, 1), (6, -9, 1)],
+            ),
+        ] {
+            let mut u = universe_with(&cells);
+            u.rule = rule.parse().unwrap();
+            for _ in 0..37 {
+                u.step();
+            }
+            let mc = macrocell::write(&u);
+
+            let mut read = Universe::default();
+            let (metadata, root) = macrocell::read(&mc, &mut read).unwrap();
+            assert_eq!(metadata.rule, u.rule.to_string());
+            assert_eq!(metadata.generation, 37);
+            *read.root_mut() = root;
+            assert_eq!(sorted_cells(&read), sorted_cells(&u));
+        }
+    }
+
+    #[test]
+    fn macrocell_malformed() {
+        let mut u = Universe::default();
+        // the child index points past the nodes read so far
+        assert!(macrocell::read("[M2]\n.*$*\n4 2 0 0 0\n", &mut u).is_none());
+        // a level 3 bitmap as the child of a level 5 node
+        assert!(macrocell::read("[M2]\n.*$*\n5 1 0 0 0\n", &mut u).is_none());
+        assert!(macrocell::read("[M2]\n#G x\n.*$*\n", &mut u).is_none());
+        assert!(macrocell::read("#R B3/S23\n.*$*\n", &mut u).is_none());
+
+        // levels go up to MAX_LEVEL and no further
+        let mc = format!("[M2]\n.*$*\n{} 1 0 0 0\n", LEAF_LEVEL + 2);
+        let mc = (LEAF_LEVEL + 3..=MAX_LEVEL).fold(mc, |mc, level| {
+            format!("{}{} {} 0 0 0\n", mc, level, level - LEAF_LEVEL - 1)
+        });
+        let (_, root) = macrocell::read(&mc, &mut u).unwrap();
+        assert_eq!(u.arena.get(root).level, MAX_LEVEL);
+        assert_eq!(u.arena.get(root).population, 2);
+        let deeper = format!("{}{} {} 0 0 0\n", mc, MAX_LEVEL + 1, MAX_LEVEL - LEAF_LEVEL);
+        assert!(macrocell::read(&deeper, &mut u).is_none());
+    }
+}
@@ -1,7 +1,49 @@
 pub const LEAF_LEVEL: u8 = 2;
 pub const LEAF_SIZE: usize = 1 << LEAF_LEVEL;
 
-pub type Leaf = [[u8; LEAF_SIZE]; LEAF_SIZE];
+// cell (x, y) is bit LEAF_SIZE * y + x of each plane, plane k holding bit k of its state.
+// two-state patterns only ever use the first plane
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Default, Debug)]
+pub struct Leaf(pub [u16; 8]);
+
+impl Leaf {
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        let i = LEAF_SIZE * y + x;
+        self.0.iter().enumerate().fold(0, |state, (k, plane)| {
+            state | (((plane >> i) & 1) as u8) << k
+        })
+    }
+    pub fn set(&mut self, x: usize, y: usize, state: u8) {
+        let i = LEAF_SIZE * y + x;
+        for (k, plane) in self.0.iter_mut().enumerate() {
+            *plane = (*plane & !(1 << i)) | (((state >> k) & 1) as u16) << i;
+        }
+    }
+    // mask of non-dead cells
+    pub fn occupied(&self) -> u16 {
+        self.0.iter().fold(0, |mask, plane| mask | plane)
+    }
+    // mask of cells in state 1, the only ones counted as neighbors
+    pub fn live(&self) -> u16 {
+        self.0[0] & !self.0[1..].iter().fold(0, |mask, plane| mask | plane)
+    }
+    pub fn population(&self) -> u64 {
+        self.occupied().count_ones() as u64
+    }
+    // (x, y, state) of every non-dead cell in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        let occupied = self.occupied();
+        (0..LEAF_SIZE * LEAF_SIZE)
+            .filter(move |i| (occupied >> i) & 1 != 0)
+            .map(|i| {
+                (
+                    i % LEAF_SIZE,
+                    i / LEAF_SIZE,
+                    self.get(i % LEAF_SIZE, i / LEAF_SIZE),
+                )
+            })
+    }
+}
 pub type NodeRef = u32;
 pub type Branch = [NodeRef; 4]; // [nw, ne, sw, se]
 
@@ -13,7 +55,7 @@ pub enum NodeKind {
 
 impl NodeKind {
     pub fn new_empty_leaf() -> Self {
-        Self::Leaf([[0; LEAF_SIZE]; LEAF_SIZE])
+        Self::Leaf(Leaf::default())
     }
     pub fn new_leaf(data: Leaf) -> Self {
         Self::Leaf(data)
@@ -112,11 +154,11 @@ impl Node {
         }
     }
     pub fn partition_points_mut(
-        points: &mut [(i64, i64)],
+        points: &mut [(i64, i64, u8)],
         level: u8,
         left: i64,
         top: i64,
-    ) -> [&mut [(i64, i64)]; 4] {
+    ) -> [&mut [(i64, i64, u8)]; 4] {
         let half = 1i64 << (level - 1);
 
         let split_y = points.iter_mut().partition_in_place(|p| p.1 < top + half);
@@ -0,0 +1,176 @@
+use crate::{
+    draw::{self, Canvas, Colors, Viewport},
+    universe::Universe,
+};
+use rustc_hash::FxHashMap;
+
+// largest side of a recorded frame, gifs get slow to encode well before the image limit
+pub const MAX_FRAME_SIZE: u32 = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordArea {
+    // whatever the viewport shows at each frame, on a canvas of this size
+    Viewport { width: u32, height: u32 },
+    Rect(i64, i64, i64, i64),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RecordOptions {
+    pub area: RecordArea,
+    pub frames: u32,
+    pub generations_per_frame: u64,
+    // pixels per cell of a rect, the viewport keeps its own
+    pub cell_size: f64,
+    pub colors: Colors,
+    // hundredths of a second each frame is shown for
+    pub delay: u16,
+}
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            area: RecordArea::Viewport {
+                width: MAX_FRAME_SIZE,
+                height: MAX_FRAME_SIZE,
+            },
+            frames: 60,
+            generations_per_frame: 1,
+            cell_size: draw::IMAGE_CELL_SIZE,
+            colors: Colors::default(),
+            delay: 10,
+        }
+    }
+}
+
+// frames are taken as the universe is stepped by whoever runs it, so steps larger than
+// generations_per_frame leave gaps between frames
+pub struct Recorder {
+    options: RecordOptions,
+    encoder: gif::Encoder<Vec<u8>>,
+    width: u32,
+    height: u32,
+    cell_size: f64,
+    frames: u32,
+    next_generation: u64,
+}
+impl Recorder {
+    pub fn new(options: RecordOptions) -> Self {
+        let (width, height, cell_size) = match options.area {
+            RecordArea::Viewport { width, height } => (
+                width.clamp(1, MAX_FRAME_SIZE),
+                height.clamp(1, MAX_FRAME_SIZE),
+                0.0,
+            ),
+            RecordArea::Rect(x1, y1, x2, y2) => {
+                let cell_size = draw::fit_cell_size(
+                    x2 - x1 + 1,
+                    y2 - y1 + 1,
+                    options.cell_size,
+                    MAX_FRAME_SIZE,
+                );
+                (
+                    ((x2 - x1 + 1) as f64 * cell_size).ceil().max(1.0) as u32,
+                    ((y2 - y1 + 1) as f64 * cell_size).ceil().max(1.0) as u32,
+                    cell_size,
+                )
+            }
+        };
+        // writing into a vec can't fail, and frames never exceed the gif size limit
+        let mut encoder = gif::Encoder::new(Vec::new(), width as u16, height as u16, &[]).unwrap();
+        encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+
+        Self {
+            options,
+            encoder,
+            width,
+            height,
+            cell_size,
+            frames: 0,
+            next_generation: 0,
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+    pub fn total_frames(&self) -> u32 {
+        self.options.frames
+    }
+    pub fn is_finished(&self) -> bool {
+        self.frames >= self.options.frames
+    }
+
+    // takes a frame once the universe reaches the next generation due,
+    // returns whether all frames have been taken
+    pub fn capture(&mut self, universe: &Universe, viewport: &Viewport) -> bool {
+        if self.is_finished() || (self.frames > 0 && universe.generation < self.next_generation) {
+            return self.is_finished();
+        }
+
+        let canvas = match self.options.area {
+            RecordArea::Viewport { width, height } => {
+                // frames smaller than the canvas keep its center
+                let mut viewport = viewport.clone();
+                viewport.origin.0 += (width as f64 - self.width as f64) / 2.0 / viewport.cell_size;
+                viewport.origin.1 +=
+                    (height as f64 - self.height as f64) / 2.0 / viewport.cell_size;
+                draw::render_viewport(
+                    universe,
+                    &viewport,
+                    self.width,
+                    self.height,
+                    &self.options.colors,
+                )
+            }
+            RecordArea::Rect(x1, y1, x2, y2) => draw::render_rect(
+                universe,
+                x1,
+                y1,
+                x2,
+                y2,
+                self.cell_size,
+                &self.options.colors,
+            ),
+        };
+        let mut frame = frame(&canvas);
+        frame.delay = self.options.delay;
+        self.encoder.write_frame(&frame).unwrap();
+
+        self.frames += 1;
+        self.next_generation = universe.generation + self.options.generations_per_frame;
+        self.is_finished()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.encoder.into_inner().unwrap()
+    }
+}
+
+// exact colors when they fit in a palette, which they do unless a rule has hundreds of states
+fn frame(canvas: &Canvas) -> gif::Frame<'static> {
+    let (width, height) = (canvas.width as u16, canvas.height as u16);
+    let mut palette = Vec::new();
+    let mut indices = FxHashMap::default();
+    let mut pixels = Vec::with_capacity(canvas.buffer.len() / 4);
+    let mut last = None;
+    for pixel in canvas.buffer.chunks_exact(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match last {
+            Some((last_color, index)) if last_color == color => index,
+            _ => {
+                let next = indices.len();
+                let index = *indices.entry(color).or_insert_with(|| {
+                    palette.extend_from_slice(&color);
+                    next
+                });
+                if index > u8::MAX as usize {
+                    let mut rgba = canvas.buffer.clone();
+                    return gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+                }
+                last = Some((color, index));
+                index
+            }
+        };
+        pixels.push(index as u8);
+    }
+    gif::Frame::from_palette_pixels(width, height, pixels, palette, None)
+}
@@ -0,0 +1,599 @@
+use std::{fmt, str::FromStr};
+
+// one representative neighborhood per Hensel letter, in canonical letter order;
+// the rest of each class are its rotations and reflections
+// neighborhoods are read row-major with the north-west cell as the most significant bit
+const HENSEL: [&[(char, u16)]; 9] = [
+    &[('c', 0b000_000_000)],
+    &[('c', 0b000_000_001), ('e', 0b000_000_010)],
+    &[
+        ('c', 0b000_000_101),
+        ('e', 0b000_001_010),
+        ('k', 0b000_001_100),
+        ('a', 0b000_000_011),
+        ('i', 0b000_101_000),
+        ('n', 0b001_000_100),
+    ],
+    &[
+        ('c', 0b001_000_101),
+        ('e', 0b000_101_010),
+        ('k', 0b001_100_010),
+        ('a', 0b000_001_011),
+        ('i', 0b000_000_111),
+        ('n', 0b000_001_101),
+        ('y', 0b001_100_001),
+        ('q', 0b001_000_110),
+        ('j', 0b000_001_110),
+        ('r', 0b000_101_001),
+    ],
+    &[
+        ('c', 0b101_000_101),
+        ('e', 0b010_101_010),
+        ('k', 0b001_100_011),
+        ('a', 0b000_001_111),
+        ('i', 0b000_101_101),
+        ('n', 0b001_000_111),
+        ('y', 0b001_100_101),
+        ('q', 0b001_100_110),
+        ('j', 0b001_101_010),
+        ('r', 0b000_101_011),
+        ('t', 0b001_101_001),
+        ('w', 0b001_001_110),
+        ('z', 0b001_101_100),
+    ],
+    &[
+        ('c', 0b010_101_011),
+        ('e', 0b101_000_111),
+        ('k', 0b011_100_101),
+        ('a', 0b001_001_111),
+        ('i', 0b000_101_111),
+        ('n', 0b001_101_011),
+        ('y', 0b010_101_101),
+        ('q', 0b001_101_110),
+        ('j', 0b001_100_111),
+        ('r', 0b001_101_101),
+    ],
+    &[
+        ('c', 0b010_101_111),
+        ('e', 0b101_001_111),
+        ('k', 0b011_100_111),
+        ('a', 0b001_101_111),
+        ('i', 0b101_101_101),
+        ('n', 0b011_101_110),
+    ],
+    &[('c', 0b011_101_111), ('e', 0b101_101_111)],
+    &[('c', 0b111_101_111)],
+];
+
+const CENTER: u16 = 0b000_010_000;
+
+fn symmetries(neighborhood: u16) -> [u16; 8] {
+    let transform = |f: fn(usize, usize) -> (usize, usize)| {
+        let mut res = 0;
+        for y in 0..3 {
+            for x in 0..3 {
+                let (tx, ty) = f(x, y);
+                res |= ((neighborhood >> (8 - (3 * y + x))) & 1) << (8 - (3 * ty + tx));
+            }
+        }
+        res
+    };
+    [
+        neighborhood,
+        transform(|x, y| (2 - y, x)),
+        transform(|x, y| (2 - x, 2 - y)),
+        transform(|x, y| (y, 2 - x)),
+        transform(|x, y| (2 - x, y)),
+        transform(|x, y| (x, 2 - y)),
+        transform(|x, y| (y, x)),
+        transform(|x, y| (2 - y, 2 - x)),
+    ]
+}
+
+// neighborhoods with at most two live cells, center included
+const SPARSE: [u64; 8] = {
+    let mut mask = [0; 8];
+    let mut i = 0;
+    while i < 512 {
+        if (i as u16).count_ones() <= 2 {
+            mask[i >> 6] |= 1 << (i & 63);
+        }
+        i += 1;
+    }
+    mask
+};
+
+// bit i of the table is whether a cell whose 3x3 neighborhood of live cells is i will be alive;
+// Generations rules add dying states 2..states that decay by one each generation
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct LifeRule {
+    table: [u64; 8],
+    states: u16,
+}
+
+impl LifeRule {
+    pub const LIFE: Self = Self::from_counts(1 << 3, (1 << 2) | (1 << 3));
+
+    // bit n of birth/survival is set if a cell with n live neighbors is born/survives
+    pub const fn from_counts(birth: u16, survival: u16) -> Self {
+        let mut table = [0; 8];
+        let mut i = 0;
+        while i < 512 {
+            let neighbors = (i as u16 & !CENTER).count_ones();
+            let mask = if i as u16 & CENTER != 0 {
+                survival
+            } else {
+                birth
+            };
+            if (mask >> neighbors) & 1 != 0 {
+                table[i >> 6] |= 1 << (i & 63);
+            }
+            i += 1;
+        }
+        Self { table, states: 2 }
+    }
+
+    pub fn states(&self) -> u16 {
+        self.states
+    }
+
+    fn is_alive(&self, neighborhood: usize) -> bool {
+        (self.table[neighborhood >> 6] >> (neighborhood & 63)) & 1 != 0
+    }
+
+    // neighborhood only has bits set for live (state 1) cells
+    pub fn next(&self, cell: u8, neighborhood: usize) -> u8 {
+        match (cell, self.is_alive(neighborhood)) {
+            (0 | 1, true) => 1,
+            (0, false) => 0,
+            (_, _) if cell as u16 + 1 < self.states => cell + 1,
+            _ => 0,
+        }
+    }
+
+    // a node with at most two live cells is guaranteed to die out in one generation,
+    // unless something can be born with fewer than three neighbors, survive alone or decay
+    pub fn kills_sparse(&self) -> bool {
+        self.states == 2 && self.table.iter().zip(SPARSE).all(|(t, s)| t & s == 0)
+    }
+
+    fn set(&mut self, neighborhood: u16) {
+        self.table[(neighborhood >> 6) as usize] |= 1 << (neighborhood & 63);
+    }
+}
+
+// parses the conditions following a B or S, e.g. "2-a3cnqy4"
+fn parse_conditions(s: &str, center: u16, rule: &mut LifeRule) -> Result<(), ()> {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let n = c.to_digit(10).filter(|&n| n <= 8).ok_or(())? as usize;
+        let negate = chars.next_if_eq(&'-').is_some();
+        let mut letters = String::new();
+        while let Some(l) = chars.next_if(|c| c.is_ascii_lowercase()) {
+            letters.push(l);
+        }
+        if (negate && letters.is_empty())
+            || letters
+                .chars()
+                .any(|l| !HENSEL[n].iter().any(|&(h, _)| h == l))
+        {
+            return Err(());
+        }
+
+        for &(letter, neighborhood) in HENSEL[n] {
+            if letters.is_empty() || letters.contains(letter) != negate {
+                for nb in symmetries(neighborhood) {
+                    rule.set(nb | center);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for LifeRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("life") {
+            return Ok(Self::LIFE);
+        }
+
+        let mut rule = Self {
+            table: [0; 8],
+            states: 2,
+        };
+        let parse_states = |s: &str| match s.parse() {
+            Ok(states @ 2..=256) => Ok(states),
+            _ => Err(()),
+        };
+        if s.contains(['B', 'b', 'S', 's']) {
+            // B3/S23, B3S23, S23/B3, B2-a/S12, B2/S/C3
+            let (mut has_birth, mut has_survival, mut has_states) = (false, false, false);
+            for part in s.split('/') {
+                if let Some(states) = part.strip_prefix(['C', 'c', 'G', 'g']) {
+                    if has_states {
+                        return Err(());
+                    }
+                    has_states = true;
+                    rule.states = parse_states(states)?;
+                    continue;
+                }
+                let mut rest = part;
+                while !rest.is_empty() {
+                    let (letter, tail) = rest.split_at(1);
+                    let len = tail.find(['B', 'b', 'S', 's']).unwrap_or(tail.len());
+                    let (seen, center) = match letter {
+                        "B" | "b" => (&mut has_birth, 0),
+                        "S" | "s" => (&mut has_survival, CENTER),
+                        _ => return Err(()),
+                    };
+                    if *seen {
+                        return Err(());
+                    }
+                    *seen = true;
+                    parse_conditions(&tail[..len], center, &mut rule)?;
+                    rest = &tail[len..];
+                }
+            }
+        } else {
+            // 23/3 (survival/birth), /2/3 (survival/birth/states)
+            let mut parts = s.split('/');
+            let (Some(survival), Some(birth)) = (parts.next(), parts.next()) else {
+                return Err(());
+            };
+            parse_conditions(birth, 0, &mut rule)?;
+            parse_conditions(survival, CENTER, &mut rule)?;
+            if let Some(states) = parts.next() {
+                rule.states = parse_states(states)?;
+            }
+            if parts.next().is_some() {
+                return Err(());
+            }
+        }
+
+        if rule.is_alive(0) {
+            // B0 would fill the infinite plane
+            return Err(());
+        }
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for LifeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conditions = |center: u16| {
+            let mut s = String::new();
+            for (n, letters) in HENSEL.iter().enumerate() {
+                let (present, missing): (Vec<_>, Vec<_>) = letters
+                    .iter()
+                    .partition(|&&(_, nb)| self.is_alive((nb | center) as usize));
+                if present.is_empty() {
+                    continue;
+                }
+                s.push(char::from(b'0' + n as u8));
+                if missing.is_empty() {
+                    continue;
+                }
+                if present.len() > missing.len() {
+                    s.push('-');
+                    s.extend(missing.iter().map(|&&(l, _)| l));
+                } else {
+                    s.extend(present.iter().map(|&&(l, _)| l));
+                }
+            }
+            s
+        };
+        write!(f, "B{}/S{}", conditions(0), conditions(CENTER))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum Neighborhood {
+    Moore,
+    VonNeumann,
+}
+
+// Larger than Life, e.g. R5,C0,M1,S34..58,B34..45,NM
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct LtlRule {
+    pub range: u16,
+    pub states: u16,
+    // whether a cell counts itself as a neighbor
+    pub middle: bool,
+    pub survival: (u32, u32),
+    pub birth: (u32, u32),
+    pub neighborhood: Neighborhood,
+}
+
+impl LtlRule {
+    pub const MAX_RANGE: u16 = 500;
+
+    pub fn neighborhood_size(&self) -> u32 {
+        let r = self.range as u32;
+        match self.neighborhood {
+            Neighborhood::Moore => (2 * r + 1) * (2 * r + 1),
+            Neighborhood::VonNeumann => 2 * r * (r + 1) + 1,
+        }
+    }
+
+    // count is the number of live (state 1) cells in the neighborhood
+    pub fn next(&self, cell: u8, count: u32) -> u8 {
+        let in_range = |(min, max): (u32, u32)| min <= count && count <= max;
+        match cell {
+            0 if in_range(self.birth) => 1,
+            0 => 0,
+            1 if in_range(self.survival) => 1,
+            _ if cell as u16 + 1 < self.states => cell + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for LtlRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut range, mut states, mut middle) = (None, 0, false);
+        let (mut survival, mut birth) = (None, None);
+        let mut neighborhood = Neighborhood::Moore;
+        let parse_range = |s: &str| {
+            let (min, max) = s.split_once("..").ok_or(())?;
+            let (min, max) = (min.parse().map_err(|_| ())?, max.parse().map_err(|_| ())?);
+            if min > max {
+                return Err(());
+            }
+            Ok((min, max))
+        };
+
+        for part in s.split(',') {
+            if part.is_empty() {
+                return Err(());
+            }
+            let (key, value) = part.split_at(1);
+            match key.to_ascii_uppercase().as_str() {
+                "R" => range = Some(value.parse::<u16>().map_err(|_| ())?),
+                "C" => states = value.parse::<u16>().map_err(|_| ())?,
+                "M" => {
+                    middle = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(()),
+                    }
+                }
+                "S" => survival = Some(parse_range(value)?),
+                "B" => birth = Some(parse_range(value)?),
+                "N" => {
+                    neighborhood = match value {
+                        "M" | "m" => Neighborhood::Moore,
+                        "N" | "n" => Neighborhood::VonNeumann,
+                        _ => return Err(()),
+                    }
+                }
+                _ => return Err(()),
+            }
+        }
+
+        let (Some(range), Some(survival), Some(birth)) = (range, survival, birth) else {
+            return Err(());
+        };
+        let rule = Self {
+            range,
+            // C0 and C1 both mean two states
+            states: states.max(2),
+            middle,
+            survival,
+            birth,
+            neighborhood,
+        };
+        let size = rule.neighborhood_size();
+        if !(1..=Self::MAX_RANGE).contains(&range)
+            || rule.states > 256
+            // B0 would fill the infinite plane
+            || birth.0 == 0
+            || birth.1 > size
+            || survival.1 > size
+        {
+            return Err(());
+        }
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for LtlRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "R{},C{},M{},S{}..{},B{}..{},N{}",
+            self.range,
+            if self.states > 2 { self.states } else { 0 },
+            self.middle as u8,
+            self.survival.0,
+            self.survival.1,
+            self.birth.0,
+            self.birth.1,
+            match self.neighborhood {
+                Neighborhood::Moore => 'M',
+                Neighborhood::VonNeumann => 'N',
+            }
+        )
+    }
+}
+
+// radius-1 rules run on HashLife, Larger than Life on its own engine
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum Rule {
+    Life(LifeRule),
+    LargerThanLife(LtlRule),
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::LIFE
+    }
+}
+
+impl Rule {
+    pub const LIFE: Self = Self::Life(LifeRule::LIFE);
+
+    pub fn states(&self) -> u16 {
+        match self {
+            Self::Life(rule) => rule.states(),
+            Self::LargerThanLife(rule) => rule.states,
+        }
+    }
+
+    // states the rule does not have are treated as alive
+    pub fn normalize(&self, state: u8) -> u8 {
+        if (state as u16) < self.states() {
+            state
+        } else {
+            1
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.is_ascii() {
+            return Err(());
+        }
+        if s.starts_with(['R', 'r']) && s[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            s.parse().map(Self::LargerThanLife)
+        } else {
+            s.parse().map(Self::Life)
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Life(rule) => rule.fmt(f),
+            Self::LargerThanLife(rule) => rule.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum Topology {
+    Plane,
+    Torus,
+    // one pair of opposite edges is joined with a twist
+    KleinBottle { twisted_rows: bool },
+}
+
+// Golly's bounded grid suffix, e.g. the T64,64 in B3/S23:T64,64
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct BoundedGrid {
+    pub topology: Topology,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BoundedGrid {
+    // inclusive, centered on the origin as in Golly
+    pub fn bounds(&self) -> (i64, i64, i64, i64) {
+        let (left, top) = (-(self.width as i64 / 2), -(self.height as i64 / 2));
+        (
+            left,
+            top,
+            left + self.width as i64 - 1,
+            top + self.height as i64 - 1,
+        )
+    }
+
+    // the cell inside the grid that a cell outside it stands for
+    pub fn wrap(&self, x: i64, y: i64) -> Option<(i64, i64)> {
+        let (left, top, right, bottom) = self.bounds();
+        let (w, h) = (self.width as i64, self.height as i64);
+        let wrap_x = |x: i64| left + (x - left).rem_euclid(w);
+        let wrap_y = |y: i64| top + (y - top).rem_euclid(h);
+        let (outside_x, outside_y) = (!(left..=right).contains(&x), !(top..=bottom).contains(&y));
+        match self.topology {
+            _ if !outside_x && !outside_y => Some((x, y)),
+            Topology::Plane => None,
+            Topology::Torus => Some((wrap_x(x), wrap_y(y))),
+            // every crossing of a twisted edge mirrors the other axis
+            Topology::KleinBottle { twisted_rows: true } if (y - top).div_euclid(h) % 2 != 0 => {
+                Some((wrap_x(left + right - x), wrap_y(y)))
+            }
+            Topology::KleinBottle {
+                twisted_rows: false,
+            } if (x - left).div_euclid(w) % 2 != 0 => Some((wrap_x(x), wrap_y(top + bottom - y))),
+            Topology::KleinBottle { .. } => Some((wrap_x(x), wrap_y(y))),
+        }
+    }
+}
+
+impl FromStr for BoundedGrid {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.is_ascii() || s.is_empty() {
+            return Err(());
+        }
+        let (kind, size) = s.split_at(1);
+        let (width, height) = size.split_once(',').unwrap_or((size, size));
+        let (width, width_twist) = width
+            .strip_suffix('*')
+            .map_or((width, false), |w| (w, true));
+        let (height, height_twist) = height
+            .strip_suffix('*')
+            .map_or((height, false), |h| (h, true));
+        let topology = match (kind, width_twist, height_twist) {
+            ("P" | "p", false, false) => Topology::Plane,
+            ("T" | "t", false, false) => Topology::Torus,
+            ("K" | "k", true, false) => Topology::KleinBottle { twisted_rows: true },
+            ("K" | "k", false, true) => Topology::KleinBottle {
+                twisted_rows: false,
+            },
+            _ => return Err(()),
+        };
+        let width = width.parse::<u32>().map_err(|_| ())?;
+        let height = height.parse::<u32>().map_err(|_| ())?;
+        // unbounded axes and shifted edges are not supported
+        if width == 0 || height == 0 || width > 1 << 30 || height > 1 << 30 {
+            return Err(());
+        }
+        Ok(Self {
+            topology,
+            width,
+            height,
+        })
+    }
+}
+
+impl fmt::Display for BoundedGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, width_twist, height_twist) = match self.topology {
+            Topology::Plane => ('P', "", ""),
+            Topology::Torus => ('T', "", ""),
+            Topology::KleinBottle { twisted_rows: true } => ('K', "*", ""),
+            Topology::KleinBottle {
+                twisted_rows: false,
+            } => ('K', "", "*"),
+        };
+        write!(
+            f,
+            "{}{}{},{}{}",
+            kind, self.width, width_twist, self.height, height_twist
+        )
+    }
+}
+
+// splits a Golly rule string like B3/S23:T64,64 into the rule and its bounded grid
+pub fn parse_rule(s: &str) -> Option<(Rule, Option<BoundedGrid>)> {
+    match s.split_once(':') {
+        Some((rule, grid)) => Some((rule.parse().ok()?, Some(grid.parse().ok()?))),
+        None => Some((s.parse().ok()?, None)),
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{
+    draw::Viewport,
+    history::{Action, History, Snapshot},
+    quadtree::{Branch, LEAF_LEVEL, Leaf, Node, NodeKind, NodeRef},
+    rule::parse_rule,
+    timeline::Timeline,
+    universe::{MAX_LEVEL, MAX_STEP, MIN_LEVEL, Universe},
+};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// bumped whenever the meaning of a field changes, other versions are refused rather than misread
+pub const VERSION: u32 = 2;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum SessionNode {
+    // the bit planes of a Leaf
+    Leaf([u16; 8]),
+    // level and the indices of [nw, ne, sw, se], which always come before their parent
+    Branch(u8, [u32; 4]),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SessionSnapshot {
+    // index into nodes
+    root: u32,
+    generation: u64,
+    step: i32,
+    action: Action,
+    parent: Option<usize>,
+    redo: Option<usize>,
+}
+
+// everything needed to pick up where the page was left, with the nodes of every
+// snapshot in one table so the history costs little more than a single pattern
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Session {
+    pub version: u32,
+    pub name: String,
+    // including the bounded grid suffix, if any
+    pub rule: String,
+    pub generation: u64,
+    pub step: i32,
+    pub tps: f64,
+    pub viewport: Viewport,
+    nodes: Vec<SessionNode>,
+    // the undo tree of Universe::history
+    history: Vec<SessionSnapshot>,
+    current: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SessionError {
+    Invalid(String),
+    UnsupportedVersion(u32),
+    UnsupportedRule(String),
+    InvalidNode(usize),
+    InvalidHistory,
+}
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Invalid(e) => write!(f, "invalid session: {}", e),
+            SessionError::UnsupportedVersion(version) => {
+                write!(f, "unsupported session version {}", version)
+            }
+            SessionError::UnsupportedRule(rule) => write!(f, "unsupported rule: {}", rule),
+            SessionError::InvalidNode(i) => write!(f, "invalid node {}", i),
+            SessionError::InvalidHistory => write!(f, "invalid history"),
+        }
+    }
+}
+impl std::error::Error for SessionError {}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+struct Writer<'a> {
+    universe: &'a Universe,
+    nodes: Vec<SessionNode>,
+    indices: FxHashMap<NodeRef, u32>,
+}
+impl Writer<'_> {
+    fn visit(&mut self, node_ref: NodeRef) -> u32 {
+        if let Some(&i) = self.indices.get(&node_ref) {
+            return i;
+        }
+        let node = self.universe.arena.get(node_ref);
+        let entry = match node.data {
+            NodeKind::Leaf(leaf) => SessionNode::Leaf(leaf.0),
+            NodeKind::Branch(children) => {
+                SessionNode::Branch(node.level, children.map(|child| self.visit(child)))
+            }
+        };
+        let i = self.nodes.len() as u32;
+        self.nodes.push(entry);
+        self.indices.insert(node_ref, i);
+        i
+    }
+}
+
+impl Session {
+    pub fn new(universe: &Universe, name: &str, tps: f64, viewport: &Viewport) -> Self {
+        let mut writer = Writer {
+            universe,
+            nodes: Vec::new(),
+            indices: FxHashMap::default(),
+        };
+        let current = universe.history.current;
+        let history = universe
+            .history
+            .snapshots
+            .iter()
+            .enumerate()
+            .map(|(i, snapshot)| SessionSnapshot {
+                root: writer.visit(snapshot.root),
+                // the current snapshot is only brought up to date when leaving it
+                generation: universe.snapshot_generation(i),
+                step: if i == current {
+                    universe.step
+                } else {
+                    snapshot.step
+                },
+                action: snapshot.action,
+                parent: snapshot.parent,
+                redo: snapshot.redo,
+            })
+            .collect();
+        let mut rule = universe.rule.to_string();
+        if let Some(grid) = &universe.grid {
+            rule.push_str(&format!(":{}", grid));
+        }
+
+        Self {
+            version: VERSION,
+            name: name.to_owned(),
+            rule,
+            generation: universe.generation,
+            step: universe.step,
+            tps,
+            viewport: viewport.clone(),
+            nodes: writer.nodes,
+            history,
+            current,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(json: &str) -> Result<Self, SessionError> {
+        // the version is checked on its own so older sessions aren't reported as malformed
+        let Header { version } =
+            serde_json::from_str(json).map_err(|e| SessionError::Invalid(e.to_string()))?;
+        if version != VERSION {
+            return Err(SessionError::UnsupportedVersion(version));
+        }
+        serde_json::from_str(json).map_err(|e| SessionError::Invalid(e.to_string()))
+    }
+
+    // replaces the pattern, history and settings of the universe.
+    // nodes are added to its arena, and nothing else changes if the session is invalid
+    pub fn restore(&self, universe: &mut Universe) -> Result<(), SessionError> {
+        let (rule, grid) = parse_rule(&self.rule)
+            .ok_or_else(|| SessionError::UnsupportedRule(self.rule.clone()))?;
+
+        let mut refs: Vec<(NodeRef, u8, u64)> = Vec::with_capacity(self.nodes.len());
+        for (i, entry) in self.nodes.iter().enumerate() {
+            let node = match *entry {
+                SessionNode::Leaf(planes) => {
+                    let leaf = Leaf(planes);
+                    if leaf
+                        .iter()
+                        .any(|(_, _, state)| state as u16 >= rule.states())
+                    {
+                        return Err(SessionError::InvalidNode(i));
+                    }
+                    Node::new_leaf(leaf, leaf.population())
+                }
+                SessionNode::Branch(level, children) => {
+                    if level <= LEAF_LEVEL || level > MAX_LEVEL {
+                        return Err(SessionError::InvalidNode(i));
+                    }
+                    let mut branch = Branch::default();
+                    let mut population = 0;
+                    for (child, index) in branch.iter_mut().zip(children) {
+                        let &(node_ref, _, child_population) = refs
+                            .get(index as usize)
+                            .filter(|&&(_, child_level, _)| child_level == level - 1)
+                            .ok_or(SessionError::InvalidNode(i))?;
+                        *child = node_ref;
+                        population += child_population;
+                    }
+                    Node::new_branch(branch, level, population)
+                }
+            };
+            let (level, population) = (node.level, node.population);
+            refs.push((universe.arena.insert(node), level, population));
+        }
+
+        // parents come before their children, and redo leads to one of them
+        let is_valid = |i: usize, snapshot: &SessionSnapshot| {
+            let is_parent_valid = match snapshot.parent {
+                Some(parent) => parent < i,
+                None => i == 0,
+            };
+            let is_redo_valid = snapshot.redo.is_none_or(|redo| {
+                self.history
+                    .get(redo)
+                    .is_some_and(|child| child.parent == Some(i))
+            });
+            is_parent_valid && is_redo_valid
+        };
+        let snapshots = self
+            .history
+            .iter()
+            .enumerate()
+            .map(|(i, snapshot)| match refs.get(snapshot.root as usize) {
+                Some(&(root, level, _)) if level >= MIN_LEVEL && is_valid(i, snapshot) => {
+                    Some(Snapshot {
+                        root,
+                        generation: snapshot.generation,
+                        step: snapshot.step.clamp(0, MAX_STEP),
+                        action: snapshot.action,
+                        parent: snapshot.parent,
+                        redo: snapshot.redo,
+                    })
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SessionError::InvalidHistory)?;
+        if self.current >= snapshots.len() {
+            return Err(SessionError::InvalidHistory);
+        }
+
+        (universe.rule, universe.grid) = (rule, grid);
+        universe.history = History {
+            snapshots,
+            current: self.current,
+        };
+        universe.generation = self.generation;
+        universe.step = self.step.clamp(0, MAX_STEP);
+        universe.timeline = Timeline::default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a glider with an undo tree that branches after a few generations
+    fn branched() -> Universe {
+        let mut u = Universe::default();
+        u.push_snapshot(Action::Paste);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            u.set(x, y, 1);
+        }
+        u.push_snapshot(Action::Run);
+        u.step = 2;
+        u.step();
+        u.undo();
+        u.push_snapshot(Action::Toggle);
+        u.set(-5, -5, 1);
+        u
+    }
+
+    fn state(u: &Universe) -> (String, u64, i32, Vec<(i64, i64, u8)>, usize, usize) {
+        (
+            u.rule.to_string(),
+            u.generation,
+            u.step,
+            u.iter_alive().collect(),
+            u.history.snapshots.len(),
+            u.history.current,
+        )
+    }
+
+    fn round_trip(session: &Session) -> Result<Session, SessionError> {
+        Session::from_json(&session.to_json())
+    }
+
+    #[test]
+    fn restores_branched_history() {
+        let u = branched();
+        let session = Session::new(&u, "glider", 30.0, &Viewport::default());
+        let mut restored = Universe::default();
+        round_trip(&session)
+            .unwrap()
+            .restore(&mut restored)
+            .unwrap();
+        assert_eq!(state(&restored), state(&u));
+        assert_eq!(restored.history.walk(), u.history.walk());
+
+        restored.undo();
+        assert_eq!(restored.history.current, 1);
+        restored.redo();
+        assert_eq!(restored.history.current, 3);
+        restored.checkout(2);
+        assert_eq!(restored.generation, 4);
+        assert_eq!(restored.iter_alive().count(), 5);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut session = Session::new(&branched(), "", 30.0, &Viewport::default());
+        session.version = VERSION + 1;
+        assert_eq!(
+            round_trip(&session).err(),
+            Some(SessionError::UnsupportedVersion(VERSION + 1))
+        );
+        assert!(matches!(
+            Session::from_json(&format!("{{\"version\": {}}}", VERSION)),
+            Err(SessionError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_sessions() {
+        let valid = Session::new(&branched(), "", 30.0, &Viewport::default());
+        let leaf = valid
+            .nodes
+            .iter()
+            .position(|node| matches!(node, SessionNode::Leaf(planes) if planes[0] != 0))
+            .unwrap();
+        let branch = valid
+            .nodes
+            .iter()
+            .position(|node| matches!(node, SessionNode::Branch(..)))
+            .unwrap();
+        let set_level = |s: &mut Session, new_level: fn(u8) -> u8| {
+            if let SessionNode::Branch(level, _) = &mut s.nodes[branch] {
+                *level = new_level(*level);
+            }
+        };
+        let cases: Vec<(Box<dyn Fn(&mut Session)>, SessionError)> = vec![
+            (
+                Box::new(|s| s.rule = "B3/S23/Q".to_owned()),
+                SessionError::UnsupportedRule("B3/S23/Q".to_owned()),
+            ),
+            // state 2 in a two-state rule
+            (
+                Box::new(|s| {
+                    if let SessionNode::Leaf(planes) = &mut s.nodes[leaf] {
+                        planes[1] = planes[0];
+                    }
+                }),
+                SessionError::InvalidNode(leaf),
+            ),
+            (
+                Box::new(|s| set_level(s, |_| LEAF_LEVEL)),
+                SessionError::InvalidNode(branch),
+            ),
+            // children that aren't one level below
+            (
+                Box::new(|s| set_level(s, |level| level + 1)),
+                SessionError::InvalidNode(branch),
+            ),
+            (
+                Box::new(|s| s.history[0].root = s.nodes.len() as u32),
+                SessionError::InvalidHistory,
+            ),
+            // a root below MIN_LEVEL
+            (
+                Box::new(|s| s.history[0].root = leaf as u32),
+                SessionError::InvalidHistory,
+            ),
+            (
+                Box::new(|s| s.history[1].parent = Some(2)),
+                SessionError::InvalidHistory,
+            ),
+            (
+                Box::new(|s| s.history[1].parent = None),
+                SessionError::InvalidHistory,
+            ),
+            (
+                Box::new(|s| s.history[2].redo = Some(3)),
+                SessionError::InvalidHistory,
+            ),
+            (
+                Box::new(|s| s.current = s.history.len()),
+                SessionError::InvalidHistory,
+            ),
+        ];
+        for (i, (corrupt, error)) in cases.into_iter().enumerate() {
+            let mut session = valid.clone();
+            corrupt(&mut session);
+            let mut u = branched();
+            let before = state(&u);
+            assert_eq!(
+                round_trip(&session).unwrap().restore(&mut u),
+                Err(error),
+                "case {}",
+                i
+            );
+            assert_eq!(state(&u), before, "case {}", i);
+        }
+    }
+}
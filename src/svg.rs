@@ -0,0 +1,194 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    draw::{self, Colors},
+    quadtree::{Node, NodeKind, NodeRef},
+    universe::Universe,
+};
+
+const GRID_COLOR: u32 = 0x404040FF;
+const BOUNDS_COLOR: u32 = 0x737373FF;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SvgOptions {
+    // only sets the default size, the image scales without losing detail
+    pub cell_size: f64,
+    pub colors: Colors,
+    pub grid_lines: bool,
+    // outline around the live cells, with a cell of margin so it isn't clipped
+    pub bounding_box: bool,
+}
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: draw::IMAGE_CELL_SIZE,
+            colors: Colors::default(),
+            grid_lines: false,
+            bounding_box: false,
+        }
+    }
+}
+
+// live cells by row, the same way _draw_node walks the tree but without culling by size
+fn collect_rows(
+    universe: &Universe,
+    node_ref: NodeRef,
+    x: i64,
+    y: i64,
+    rect: (i64, i64, i64, i64),
+    rows: &mut BTreeMap<i64, Vec<(i64, u8)>>,
+) {
+    let node = universe.arena.get(node_ref);
+    let size = 1i64 << node.level;
+    let (x1, y1, x2, y2) = rect;
+    if node.population == 0 || x + size <= x1 || y + size <= y1 || x > x2 || y > y2 {
+        return;
+    }
+
+    match &node.data {
+        NodeKind::Leaf(leaf) => {
+            for (j, i, cell) in leaf.iter() {
+                let (cx, cy) = (x + j as i64, y + i as i64);
+                if (x1..=x2).contains(&cx) && (y1..=y2).contains(&cy) {
+                    rows.entry(cy).or_default().push((cx, cell));
+                }
+            }
+        }
+        NodeKind::Branch(children) => {
+            for (i, child) in children.iter().enumerate() {
+                let (ox, oy) = Node::get_child_offset(i, node.level);
+                collect_rows(universe, *child, x + ox, y + oy, rect, rows);
+            }
+        }
+    }
+}
+
+// #rrggbb, plus an opacity attribute when the color isn't opaque
+fn fill(color: u32) -> String {
+    let alpha = color & 0xFF;
+    if alpha == 0xFF {
+        format!("fill=\"#{:06x}\"", color >> 8)
+    } else {
+        format!(
+            "fill=\"#{:06x}\" fill-opacity=\"{:.3}\"",
+            color >> 8,
+            alpha as f64 / 255.0
+        )
+    }
+}
+
+// one rect per horizontal run of cells in the same state, in cell units
+pub fn render(
+    universe: &Universe,
+    x1: i64,
+    y1: i64,
+    x2: i64,
+    y2: i64,
+    options: &SvgOptions,
+) -> String {
+    let mut rows = BTreeMap::new();
+    let half = 1i64 << (universe.level() - 1);
+    collect_rows(
+        universe,
+        universe.root(),
+        -half,
+        -half,
+        (x1, y1, x2, y2),
+        &mut rows,
+    );
+
+    // runs grouped by state so each color is written once
+    let mut runs = BTreeMap::<u8, Vec<(i64, i64, i64)>>::new();
+    for (&y, row) in rows.iter_mut() {
+        row.sort_unstable();
+        let mut run: Option<(i64, i64, u8)> = None;
+        for &(x, state) in row.iter() {
+            match &mut run {
+                Some((_, end, run_state)) if *end == x && *run_state == state => *end += 1,
+                _ => {
+                    if let Some((start, end, run_state)) = run {
+                        runs.entry(run_state)
+                            .or_default()
+                            .push((start, y, end - start));
+                    }
+                    run = Some((x, x + 1, state));
+                }
+            }
+        }
+        if let Some((start, end, run_state)) = run {
+            runs.entry(run_state)
+                .or_default()
+                .push((start, y, end - start));
+        }
+    }
+
+    let margin = if options.bounding_box { 1 } else { 0 };
+    let (left, top) = (x1 - margin, y1 - margin);
+    let (width, height) = (x2 - x1 + 1 + 2 * margin, y2 - y1 + 1 + 2 * margin);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">",
+        width,
+        height,
+        width as f64 * options.cell_size,
+        height as f64 * options.cell_size,
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" {}/>",
+        width,
+        height,
+        fill(options.colors.background)
+    );
+    for (state, runs) in runs {
+        let color = draw::state_color(options.colors.alive, state, universe.rule.states());
+        let _ = writeln!(svg, "<g {}>", fill(color));
+        for (x, y, length) in runs {
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\"/>",
+                x - left,
+                y - top,
+                length
+            );
+        }
+        svg.push_str("</g>\n");
+    }
+
+    if options.grid_lines {
+        let mut path = String::new();
+        for x in margin..=width - margin {
+            let _ = write!(path, "M{} {}V{}", x, margin, height - margin);
+        }
+        for y in margin..=height - margin {
+            let _ = write!(path, "M{} {}H{}", margin, y, width - margin);
+        }
+        let _ = writeln!(
+            svg,
+            "<path d=\"{}\" stroke=\"#{:06x}\" stroke-width=\"0.05\" fill=\"none\"/>",
+            path,
+            GRID_COLOR >> 8
+        );
+    }
+    if options.bounding_box && !rows.is_empty() {
+        let (bx1, bx2) = rows
+            .values()
+            .flatten()
+            .fold((i64::MAX, i64::MIN), |(bx1, bx2), &(x, _)| {
+                (bx1.min(x), bx2.max(x))
+            });
+        let (by1, by2) = (*rows.keys().next().unwrap(), *rows.keys().last().unwrap());
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" stroke=\"#{:06x}\" stroke-width=\"0.1\" fill=\"none\"/>",
+            bx1 - left,
+            by1 - top,
+            bx2 - bx1 + 1,
+            by2 - by1 + 1,
+            BOUNDS_COLOR >> 8
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
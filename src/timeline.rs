@@ -0,0 +1,97 @@
+use crate::{quadtree::NodeRef, universe::Universe};
+
+// keyframes kept before thinning them out
+pub const TIMELINE_CAPACITY: usize = 1 << 10;
+
+// roots of the current run at regular generations.
+// any generation since the start of the run is replayed from the keyframe before it
+pub struct Timeline {
+    pub keyframes: Vec<(u64, NodeRef)>,
+    pub interval: u64,
+    // the last (generation, root) the timeline produced,
+    // anything else means the pattern was edited and a new run began
+    pub head: Option<(u64, NodeRef)>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            interval: 1,
+            head: None,
+        }
+    }
+}
+
+impl Universe {
+    pub(crate) fn sync_timeline(&mut self) {
+        let head = (self.generation, self.root());
+        if self.timeline.head != Some(head) {
+            self.timeline = Timeline {
+                keyframes: vec![head],
+                head: Some(head),
+                ..Default::default()
+            };
+        }
+    }
+    pub(crate) fn record_timeline(&mut self) {
+        let head = (self.generation, self.root());
+        let timeline = &mut self.timeline;
+        timeline.head = Some(head);
+        let last = timeline
+            .keyframes
+            .last()
+            .map_or(0, |&(generation, _)| generation);
+        if self.generation < last + timeline.interval {
+            return;
+        }
+        timeline.keyframes.push(head);
+        if timeline.keyframes.len() > TIMELINE_CAPACITY {
+            // keep the start of the run and every other keyframe after it
+            let mut i = 0;
+            timeline.keyframes.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            timeline.interval *= 2;
+        }
+    }
+
+    // whether the pattern changed since the timeline last saw it, so stepping begins a new run
+    pub fn is_edited(&self) -> bool {
+        self.timeline.head != Some((self.generation, self.root()))
+    }
+    // first and last generation the current run can seek to
+    pub fn timeline_range(&self) -> (u64, u64) {
+        if self.is_edited() {
+            return (self.generation, self.generation);
+        }
+        let (start, _) = self.timeline.keyframes[0];
+        let (last, _) = *self.timeline.keyframes.last().unwrap();
+        (start, last.max(self.generation))
+    }
+    pub fn seek(&mut self, generation: u64) {
+        self.sync_timeline();
+        let keyframes = &self.timeline.keyframes;
+        let i = keyframes.partition_point(|&(g, _)| g <= generation);
+        if i == 0 || generation == self.generation {
+            return;
+        }
+        // replay from the current generation if it is closer than the keyframe
+        let (g, root) = keyframes[i - 1];
+        if !(g..generation).contains(&self.generation) {
+            *self.root_mut() = root;
+            self.generation = g;
+            self.timeline.head = Some((g, root));
+        }
+        self.advance_to(generation);
+    }
+    pub fn can_step_back(&self) -> bool {
+        self.timeline_range().0 < self.generation
+    }
+    pub fn step_back(&mut self) {
+        if self.can_step_back() {
+            self.seek(self.generation - 1);
+        }
+    }
+}
@@ -2,34 +2,72 @@ use std::collections::VecDeque;
 
 use crate::{
     arena::Arena,
+    cache::Cache,
+    history::History,
     quadtree::{Branch, LEAF_LEVEL, LEAF_SIZE, Leaf, Node, NodeKind, NodeRef},
+    rule::{BoundedGrid, LifeRule, Rule, Topology},
+    timeline::Timeline,
 };
-use rustc_hash::FxHashMap;
 
-pub fn step_grid(grid: &[Vec<u8>], res: &mut [Vec<u8>]) {
+pub fn step_grid(grid: &[Vec<u8>], res: &mut [Vec<u8>], rule: &LifeRule) {
     for i in 1..(grid.len() - 1) {
         for j in 1..(grid[0].len() - 1) {
-            let neighbors = grid[i - 1][j - 1]
-                + grid[i - 1][j]
-                + grid[i - 1][j + 1]
-                + grid[i][j - 1]
-                + grid[i][j + 1]
-                + grid[i + 1][j - 1]
-                + grid[i + 1][j]
-                + grid[i + 1][j + 1];
-
-            res[i][j] = if neighbors == 2 {
-                grid[i][j]
-            } else if neighbors == 3 {
-                1
-            } else {
-                0
-            };
+            // 3x3 neighborhood of live cells,
+            // row-major with the north-west cell as the most significant bit
+            let mut neighborhood = 0;
+            for row in &grid[(i - 1)..=(i + 1)] {
+                for cell in &row[(j - 1)..=(j + 1)] {
+                    neighborhood = (neighborhood << 1) | (*cell == 1) as usize;
+                }
+            }
+
+            res[i][j] = rule.next(grid[i][j], neighborhood);
+        }
+    }
+}
+
+// next generation of the center 2x2 of every 4x4 square,
+// indexed and returned as bit 4 * y + x and bit 2 * y + x
+fn leaf_table(rule: &LifeRule) -> Vec<u8> {
+    (0..1usize << 16)
+        .map(|square| {
+            let mut center = 0;
+            for y in 1..3 {
+                for x in 1..3 {
+                    let mut neighborhood = 0;
+                    for ny in (y - 1)..=(y + 1) {
+                        for nx in (x - 1)..=(x + 1) {
+                            neighborhood = (neighborhood << 1) | ((square >> (4 * ny + nx)) & 1);
+                        }
+                    }
+                    let cell = ((square >> (4 * y + x)) & 1) as u8;
+                    center |= rule.next(cell, neighborhood) << (2 * (y - 1) + (x - 1));
+                }
+            }
+            center
+        })
+        .collect()
+}
+
+// steps the 4x4 windows of an 8x8 square with their top left corners at offsets,
+// writing each window's center back into the square
+fn step_square(square: u64, offsets: &[usize], table: &[u8]) -> u64 {
+    let mut next = 0;
+    for &y in offsets {
+        for &x in offsets {
+            let mut window = 0;
+            for i in 0..4 {
+                window |= ((square >> (8 * (y + i) + x)) & 0xF) << (4 * i);
+            }
+            let center = table[window as usize] as u64;
+            next |= (center & 0b11) << (8 * (y + 1) + x + 1);
+            next |= (center >> 2) << (8 * (y + 2) + x + 1);
         }
     }
+    next
 }
 
-type Key = (NodeKind, i32); // (node, generations)
+type Key = (NodeKind, i32, LifeRule); // (node, generations, rule)
 enum Bound {
     Top,
     Left,
@@ -44,15 +82,32 @@ pub enum InsertMode {
 }
 pub struct Universe {
     pub arena: Arena<Node, NodeKind>,
-    pub cache: FxHashMap<Key, (NodeRef, u64)>,
+    pub cache: Cache<Key, (NodeRef, u64)>,
     pub empty_ref: Vec<NodeRef>,
     pub generation: u64,
     pub step: i32,
-    pub history: Vec<NodeRef>,
-    pub history_index: usize,
+    pub rule: Rule,
+    leaf_table: Option<(LifeRule, Vec<u8>)>,
+    // finite grid from a rule suffix like :T64,64, the plane is unbounded otherwise
+    pub grid: Option<BoundedGrid>,
+    pub history: History,
+    pub timeline: Timeline,
+    // nodes allowed in the arena before stepping collects garbage
+    pub arena_budget: usize,
 }
 
 pub const ARENA_SIZE: usize = 1 << 10;
+pub const ARENA_BUDGET: usize = 1 << 20;
+pub const CACHE_SIZE: usize = 1 << 18;
+// the root shrinks no further than this while stepping
+pub const MIN_LEVEL: u8 = LEAF_LEVEL + 4;
+// largest root whose coordinates still fit in an i64
+pub const MAX_LEVEL: u8 = 62;
+pub const MAX_STEP: i32 = MAX_LEVEL as i32 - 2;
+// engines without hashlife do 2^step single generations per step, so they stop well short of it
+pub const MAX_SLOW_STEP: i32 = 4;
+// generations the slow engines are asked to cover at once
+pub const MAX_SLOW_ADVANCE: u64 = 1 << 12;
 impl Default for Universe {
     fn default() -> Self {
         Self::with_size(10)
@@ -70,32 +125,33 @@ impl Universe {
             empty_ref[level as usize] = arena.insert(node);
         }
         let root = empty_ref[size as usize];
-        let mut cache = FxHashMap::default();
-        cache.reserve(capacity);
-        let history = vec![root];
         Self {
             arena,
-            cache,
+            cache: Cache::new(CACHE_SIZE),
             empty_ref,
             generation: 0,
             step: 0,
-            history,
-            history_index: 0,
+            rule: Rule::default(),
+            leaf_table: None,
+            grid: None,
+            history: History::new(root),
+            timeline: Timeline::default(),
+            arena_budget: ARENA_BUDGET.max(capacity),
         }
     }
     pub fn with_size(size: u8) -> Self {
         Self::with_size_and_arena_capacity(size, ARENA_SIZE)
     }
     pub fn root(&self) -> NodeRef {
-        self.history[self.history_index]
+        self.history.snapshots[self.history.current].root
     }
     pub fn root_mut(&mut self) -> &mut NodeRef {
-        &mut self.history[self.history_index]
+        &mut self.history.snapshots[self.history.current].root
     }
 
     pub fn _set_points(
         &mut self,
-        points: &mut [(i64, i64)],
+        points: &mut [(i64, i64, u8)],
         x1: i64,
         y1: i64,
         x2: i64,
@@ -128,11 +184,12 @@ impl Universe {
                         unreachable!()
                     }
                     InsertMode::Or => {
-                        for (x, y) in points {
-                            if data[(*y - top) as usize][(*x - left) as usize] == 0 {
+                        for (x, y, state) in points {
+                            let (x, y) = ((*x - left) as usize, (*y - top) as usize);
+                            if data.get(x, y) == 0 {
                                 pop += 1;
                             }
-                            data[(*y - top) as usize][(*x - left) as usize] = 1;
+                            data.set(x, y, *state);
                         }
                     }
                 }
@@ -168,15 +225,25 @@ impl Universe {
     }
     pub fn set_points(
         &mut self,
-        points: &[(i64, i64)],
+        points: &[(i64, i64, u8)],
         x1: i64,
         y1: i64,
         x2: i64,
         y2: i64,
         mut mode: &InsertMode,
     ) {
+        if let Some((px1, py1, px2, py2)) = points.iter().fold(None, |rect, &(x, y, _)| {
+            let (x1, y1, x2, y2) = rect.unwrap_or((x, y, x, y));
+            Some((x1.min(x), y1.min(y), x2.max(x), y2.max(y)))
+        }) {
+            self.expand_to(px1, py1, px2, py2);
+        }
         let q = 1i64 << (self.level() - 2);
-        let mut points = points.to_owned();
+        let mut points = points
+            .iter()
+            .filter(|&&(x, y, _)| self.contains(x, y, x, y))
+            .map(|&(x, y, state)| (x, y, self.rule.normalize(state)))
+            .collect::<Vec<_>>();
         if *mode == InsertMode::Copy {
             self.clear_rect(x1, y1, x2, y2);
             mode = &InsertMode::Or;
@@ -196,6 +263,21 @@ impl Universe {
             .0;
     }
 
+    // replaces the pattern with cells read from a file, which start at (0, 0).
+    // on a bounded grid they are centered like the grid is, as in Golly
+    pub fn load_cells(&mut self, cells: &[(i64, i64, u8)], width: u32, height: u32) {
+        let (dx, dy) = match self.grid {
+            Some(_) => (-(width as i64 / 2), -(height as i64 / 2)),
+            None => (0, 0),
+        };
+        let cells = cells
+            .iter()
+            .map(|&(x, y, state)| (x + dx, y + dy, state))
+            .collect::<Vec<_>>();
+        let half = 1i64 << (self.level() - 1);
+        self.set_points(&cells, -half, -half, half - 1, half - 1, &InsertMode::Copy);
+    }
+
     pub fn new_node(&self, level: u8) -> Node {
         if level == LEAF_LEVEL {
             Node::new_empty_leaf()
@@ -263,6 +345,10 @@ impl Universe {
         }
     }
     pub fn set_node(&mut self, x: i64, y: i64, level: u8, node_ref: NodeRef) {
+        self.expand_to(x, y, x, y);
+        while self.level() <= level {
+            *self.root_mut() = self.grown(self.root());
+        }
         *self.root_mut() = self._set_node(x, y, level, node_ref, self.root()).0;
     }
 
@@ -306,10 +392,11 @@ impl Universe {
             let mut data = Leaf::default();
             let h = (LEAF_SIZE / 2) as i64;
             let mut pop = 0;
-            for (y, row) in data.iter_mut().enumerate() {
-                for (x, cell) in row.iter_mut().enumerate() {
-                    *cell = self._get(x as i64 - h, y as i64 - h, node_ref);
-                    pop += *cell as u64;
+            for y in 0..LEAF_SIZE {
+                for x in 0..LEAF_SIZE {
+                    let cell = self._get(x as i64 - h, y as i64 - h, node_ref);
+                    data.set(x, y, cell);
+                    pop += (cell != 0) as u64;
                 }
             }
             Node::new_leaf(data, pop)
@@ -332,65 +419,299 @@ impl Universe {
         (self.arena.insert(new_node), new_node.population)
     }
 
+    // whether the inclusive rect lies within the root
+    pub fn contains(&self, x1: i64, y1: i64, x2: i64, y2: i64) -> bool {
+        let half = 1i64 << (self.level() - 1);
+        -half <= x1 && -half <= y1 && x2 < half && y2 < half
+    }
+    // grows the root around its center until it contains the inclusive rect
+    pub fn expand_to(&mut self, x1: i64, y1: i64, x2: i64, y2: i64) {
+        while !self.contains(x1, y1, x2, y2) && self.level() < MAX_LEVEL {
+            *self.root_mut() = self.grown(self.root());
+        }
+    }
+    // population outside the center quarter of the root
+    fn border_population(&self) -> u64 {
+        let node = self.arena.get(self.root());
+        let center = (0..4)
+            .map(|i| {
+                let child = self.arena.get(node.data.as_branch()[i]);
+                self.arena.get(child.data.as_branch()[3 - i]).population
+            })
+            .sum::<u64>();
+        node.population - center
+    }
+    // grows the root until nothing can reach its border within 2^step generations
+    fn expand_for_step(&mut self, step: i32) {
+        while self.level() < MAX_LEVEL
+            && ((self.level() as i32) < step + 2 || self.border_population() != 0)
+        {
+            *self.root_mut() = self.grown(self.root());
+        }
+    }
+    fn shrink_to_fit(&mut self, step: i32) {
+        let min_level = (MIN_LEVEL as i32).max(step + 2);
+        while (self.level() as i32) > min_level && self.border_population() == 0 {
+            *self.root_mut() = self.shrunk(self.root()).0;
+        }
+    }
+
+    // mark-and-compact, rooted at the history, the timeline and the empty nodes.
+    // any other NodeRef held outside the universe is invalidated
+    pub fn collect_garbage(&mut self) {
+        let mut marked = vec![false; self.arena.len()];
+        let mut stack = self
+            .history
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.root)
+            .chain(self.empty_ref.iter().copied())
+            .chain(self.timeline.keyframes.iter().map(|&(_, root)| root))
+            .chain(self.timeline.head.map(|(_, root)| root))
+            .collect::<Vec<_>>();
+        while let Some(node_ref) = stack.pop() {
+            if std::mem::replace(&mut marked[node_ref as usize], true) {
+                continue;
+            }
+            if let NodeKind::Branch(children) = self.arena.get(node_ref).data {
+                stack.extend(children);
+            }
+        }
+
+        let moved = self.arena.compact(&marked, |node, moved| {
+            if let NodeKind::Branch(children) = &mut node.data {
+                for child in children {
+                    *child = moved[*child as usize].unwrap();
+                }
+            }
+        });
+        let remap = |node_ref: NodeRef| moved[node_ref as usize];
+
+        for node_ref in self
+            .history
+            .snapshots
+            .iter_mut()
+            .map(|snapshot| &mut snapshot.root)
+            .chain(&mut self.empty_ref)
+            .chain(self.timeline.keyframes.iter_mut().map(|(_, root)| root))
+            .chain(self.timeline.head.as_mut().map(|(_, root)| root))
+        {
+            *node_ref = remap(*node_ref).unwrap();
+        }
+        // memoized steps survive only if every node they mention does
+        self.cache
+            .retain_map(|(data, step, rule), (result, population)| {
+                let data = match data {
+                    NodeKind::Leaf(_) => data,
+                    NodeKind::Branch(children) => NodeKind::Branch([
+                        remap(children[0])?,
+                        remap(children[1])?,
+                        remap(children[2])?,
+                        remap(children[3])?,
+                    ]),
+                };
+                Some(((data, step, rule), (remap(result)?, population)))
+            });
+
+        // leave headroom so a large live set does not collect on every step
+        self.arena_budget = self.arena_budget.max(2 * self.arena.len());
+    }
+
+    // largest step the rule's engine takes without stalling
+    pub fn max_step(&self) -> i32 {
+        match self.rule {
+            _ if self.grid.is_some() => MAX_SLOW_STEP,
+            Rule::Life(_) => MAX_STEP,
+            Rule::LargerThanLife(_) => MAX_SLOW_STEP,
+        }
+    }
+
     pub fn step(&mut self) {
-        let step = self
-            .step
-            .min((self.arena.get(self.root()).level - 2) as i32);
-        let root_ref = self.grown(self.root());
-        let next = self.step_node(root_ref, self.step).0;
+        if self.arena.len() > self.arena_budget {
+            self.collect_garbage();
+        }
+        self.sync_timeline();
+        let step = self.step.clamp(0, self.max_step());
+        match self.rule {
+            _ if let Some(grid) = self.grid => {
+                for _ in 0..(1u64 << step) {
+                    self.step_bounded(&grid);
+                }
+            }
+            Rule::Life(rule) => {
+                self.expand_for_step(step);
+                let root_ref = self.grown(self.root());
+                let next = self.step_node(root_ref, step, &rule).0;
+                *self.root_mut() = next;
+            }
+            Rule::LargerThanLife(rule) => {
+                for _ in 0..(1u64 << step) {
+                    self.step_ltl(&rule);
+                }
+            }
+        }
         self.generation += 1u64 << step;
-        *self.root_mut() = next;
+        self.shrink_to_fit(step);
+        self.record_timeline();
     }
 
-    fn step_node(&mut self, curr: NodeRef, mut step: i32) -> (NodeRef, u64) {
+    // advances exactly n generations, largest power of two steps first
+    pub fn advance_by(&mut self, mut n: u64) {
+        let step = self.step;
+        while n > 0 {
+            self.step = (n.ilog2() as i32).min(self.max_step());
+            n -= 1 << self.step;
+            self.step();
+        }
+        self.step = step;
+    }
+    // generations already behind are left alone
+    pub fn advance_to(&mut self, generation: u64) {
+        self.advance_by(generation.saturating_sub(self.generation));
+    }
+
+    // advances a single generation on a finite grid.
+    // cells just outside the edges are filled in from the cells they wrap around to,
+    // stepped along with the grid, then cleared
+    fn step_bounded(&mut self, grid: &BoundedGrid) {
+        let (left, top, right, bottom) = grid.bounds();
+        let r = match self.rule {
+            Rule::Life(_) => 1,
+            Rule::LargerThanLife(rule) => rule.range as i64,
+        };
+        let (x1, y1, x2, y2) = (left - r, top - r, right + r, bottom + r);
+        let (w, h) = (grid.width as i64, grid.height as i64);
+        let mut edge_cells = [
+            (left, top, right, top + r - 1),
+            (left, bottom - r + 1, right, bottom),
+            (left, top + r, left + r - 1, bottom - r),
+            (right - r + 1, top + r, right, bottom - r),
+        ]
+        .iter()
+        .flat_map(|&(x1, y1, x2, y2)| {
+            self.iter_alive_in_rect(x1.max(left), y1.max(top), x2.min(right), y2.min(bottom))
+        })
+        .collect::<Vec<_>>();
+        edge_cells.sort_unstable();
+        edge_cells.dedup();
+
+        let mut ghosts = Vec::new();
+        for (x, y, state) in edge_cells {
+            // candidate images across each edge and corner,
+            // mirrored when a twisted edge is crossed an odd number of times
+            let (kx, ky) = ((r + w - 1) / w, (r + h - 1) / h);
+            for (dx, dy) in (-kx..=kx).flat_map(|dx| (-ky..=ky).map(move |dy| (dx, dy))) {
+                let (mx, my) = (left + right - x, top + bottom - y);
+                let (gx, gy) = match grid.topology {
+                    Topology::KleinBottle { twisted_rows: true } if dy % 2 != 0 => (mx, y),
+                    Topology::KleinBottle {
+                        twisted_rows: false,
+                    } if dx % 2 != 0 => (x, my),
+                    _ => (x, y),
+                };
+                let (gx, gy) = (gx + dx * w, gy + dy * h);
+                let outside = !(left..=right).contains(&gx) || !(top..=bottom).contains(&gy);
+                if outside
+                    && (x1..=x2).contains(&gx)
+                    && (y1..=y2).contains(&gy)
+                    && grid.wrap(gx, gy) == Some((x, y))
+                {
+                    ghosts.push((gx, gy, state));
+                }
+            }
+        }
+        self.set_points(&ghosts, x1, y1, x2, y2, &InsertMode::Or);
+
+        match self.rule {
+            Rule::Life(rule) => {
+                self.expand_for_step(0);
+                let root_ref = self.grown(self.root());
+                *self.root_mut() = self.step_node(root_ref, 0, &rule).0;
+            }
+            Rule::LargerThanLife(rule) => self.step_ltl(&rule),
+        }
+
+        let half = 1i64 << (self.level() - 1);
+        self.clear_rect(-half, -half, half - 1, top - 1);
+        self.clear_rect(-half, bottom + 1, half - 1, half - 1);
+        self.clear_rect(-half, top, left - 1, bottom);
+        self.clear_rect(right + 1, top, half - 1, bottom);
+    }
+
+    fn step_node(&mut self, curr: NodeRef, mut step: i32, rule: &LifeRule) -> (NodeRef, u64) {
         let node = self.arena.get(curr);
         let level = node.level;
         step = step.min(node.level as i32 - 2);
 
-        if node.population < 3 {
+        if node.population == 0 || (node.population < 3 && rule.kills_sparse()) {
             return (self.empty_ref[(level - 1) as usize], 0);
         }
-        let key: Key = (node.data, step);
+        let key: Key = (node.data, step, *rule);
         if let Some(&n) = self.cache.get(&key) {
             return n;
         }
 
         let (new_node, population) = if level == LEAF_LEVEL + 1 {
-            const SIZE: usize = 1 << (LEAF_LEVEL + 1);
-            // pad by 1 cell
-            let mut data = vec![vec![0; SIZE + 2]; SIZE + 2];
-            for ci in 0..2 {
-                for cj in 0..2 {
-                    let child_data = self
-                        .arena
-                        .get(node.data.as_branch()[2 * ci + cj])
-                        .data
-                        .as_leaf();
-                    for i in 0..LEAF_SIZE {
-                        for j in 0..LEAF_SIZE {
-                            data[1 + ci * LEAF_SIZE + i][1 + cj * LEAF_SIZE + j] = child_data[i][j];
-                        }
+            let children = node
+                .data
+                .as_branch()
+                .map(|child| *self.arena.get(child).data.as_leaf());
+            // advance min(step, 2^(LEAF_LEVEL - 1)) steps
+            let generations = 1 << step.min((LEAF_LEVEL - 1) as i32);
+
+            let leaf_data = if rule.states() == 2 {
+                if self.leaf_table.as_ref().is_none_or(|(r, _)| r != rule) {
+                    self.leaf_table = Some((*rule, leaf_table(rule)));
+                }
+                let table = &self.leaf_table.as_ref().unwrap().1;
+
+                // the 8x8 square as bit 8 * y + x
+                let mut grid = 0u64;
+                for (i, child) in children.iter().enumerate() {
+                    let live = child.live() as u64;
+                    for y in 0..LEAF_SIZE {
+                        let row = (live >> (LEAF_SIZE * y)) & 0xF;
+                        grid |= row << (8 * (LEAF_SIZE * (i / 2) + y) + LEAF_SIZE * (i % 2));
                     }
                 }
-            }
+                if generations == 2 {
+                    grid = step_square(grid, &[0, 2, 4], table);
+                }
+                grid = step_square(grid, &[1, 3], table);
 
-            // advance min(step, 2^(LEAF_LEVEL - 1)) steps
-            let mut next_data = data.clone();
-            for _ in 0..(1 << step.min((LEAF_LEVEL - 1) as i32)) {
-                step_grid(&data, &mut next_data);
-                std::mem::swap(&mut data, &mut next_data);
-            }
+                let mut leaf_data = Leaf::default();
+                for y in 0..LEAF_SIZE {
+                    leaf_data.0[0] |=
+                        (((grid >> (8 * (y + 2) + 2)) & 0xF) as u16) << (LEAF_SIZE * y);
+                }
+                leaf_data
+            } else {
+                const SIZE: usize = 1 << (LEAF_LEVEL + 1);
+                // pad by 1 cell
+                let mut data = vec![vec![0; SIZE + 2]; SIZE + 2];
+                for (i, child) in children.iter().enumerate() {
+                    for (x, y, state) in child.iter() {
+                        data[1 + (i / 2) * LEAF_SIZE + y][1 + (i % 2) * LEAF_SIZE + x] = state;
+                    }
+                }
 
-            let mut leaf_data: Leaf = Leaf::default();
-            let mut pop = 0;
-            for i in 0..LEAF_SIZE {
-                for j in 0..LEAF_SIZE {
-                    let shift = 1 + LEAF_SIZE / 2;
-                    leaf_data[i][j] = data[shift + i][shift + j];
-                    pop += leaf_data[i][j] as u64;
+                let mut next_data = data.clone();
+                for _ in 0..generations {
+                    step_grid(&data, &mut next_data, rule);
+                    std::mem::swap(&mut data, &mut next_data);
                 }
-            }
-            let new_node = Node::new_leaf(leaf_data, pop);
+
+                let mut leaf_data = Leaf::default();
+                let shift = 1 + LEAF_SIZE / 2;
+                for y in 0..LEAF_SIZE {
+                    for x in 0..LEAF_SIZE {
+                        leaf_data.set(x, y, data[shift + y][shift + x]);
+                    }
+                }
+                leaf_data
+            };
+
+            let new_node = Node::new_leaf(leaf_data, leaf_data.population());
             (self.arena.insert(new_node), new_node.population)
         } else {
             // everything is in row major order
@@ -422,7 +743,7 @@ impl Universe {
                         sub_16_pop[k] + sub_16_pop[k + 1] + sub_16_pop[k + 4] + sub_16_pop[k + 5],
                     ));
                     (sub_9[3 * i + j], sub_9_pop[3 * i + j]) =
-                        self.step_node(sub_9[3 * i + j], step);
+                        self.step_node(sub_9[3 * i + j], step, rule);
                 }
             }
 
@@ -441,7 +762,7 @@ impl Universe {
             let mut pop = 0;
             if step + 2 >= level as i32 {
                 for sub in &mut sub_4 {
-                    let (stepped, p) = self.step_node(*sub, step);
+                    let (stepped, p) = self.step_node(*sub, step, rule);
                     *sub = stepped;
                     pop += p;
                 }
@@ -472,13 +793,18 @@ impl Universe {
         }
 
         if node.is_leaf() {
-            node.data.as_leaf()[(y + half) as usize][(x + half) as usize]
+            node.data
+                .as_leaf()
+                .get((x + half) as usize, (y + half) as usize)
         } else {
             let (cx, cy) = Node::normalize_coords(x, y, node.level - 1);
             self._get(cx, cy, node.get_child(x, y))
         }
     }
     pub fn get(&self, x: i64, y: i64) -> u8 {
+        if !self.contains(x, y, x, y) {
+            return 0;
+        }
         self._get(x, y, self.root())
     }
     fn _set(&mut self, x: i64, y: i64, value: u8, curr: NodeRef) -> (NodeRef, i64) {
@@ -500,9 +826,10 @@ impl Universe {
             }
             NodeKind::Leaf(data) => {
                 let s = (LEAF_SIZE / 2) as i64;
-                dpop -= data[(y + s) as usize][(x + s) as usize] as i64;
-                data[(y + s) as usize][(x + s) as usize] = value;
-                dpop += data[(y + s) as usize][(x + s) as usize] as i64;
+                let (x, y) = ((x + s) as usize, (y + s) as usize);
+                dpop -= (data.get(x, y) != 0) as i64;
+                data.set(x, y, value);
+                dpop += (data.get(x, y) != 0) as i64;
             }
         };
         population = (population as i64 + dpop) as u64;
@@ -517,6 +844,10 @@ impl Universe {
         )
     }
     pub fn set(&mut self, x: i64, y: i64, value: u8) {
+        self.expand_to(x, y, x, y);
+        if !self.contains(x, y, x, y) {
+            return;
+        }
         *self.root_mut() = self._set(x, y, value, self.root()).0;
         self.generation = 0
     }
@@ -542,12 +873,10 @@ impl Universe {
                 if node.level != LEAF_LEVEL {
                     return;
                 }
-                for (i, row) in v.iter().enumerate() {
-                    for (j, cell) in row.iter().enumerate() {
-                        let (x, y) = ((j as i64 - x1 - 1) as usize, (i as i64 - y1 - 1) as usize);
-                        if x < w && y < h {
-                            grid[y][x] = *cell;
-                        }
+                for (j, i, cell) in v.iter() {
+                    let (x, y) = ((j as i64 - x1 - 1) as usize, (i as i64 - y1 - 1) as usize);
+                    if x < w && y < h {
+                        grid[y][x] = cell;
                     }
                 }
             }
@@ -586,17 +915,15 @@ impl Universe {
 
         let new_node = match node.data {
             NodeKind::Leaf(mut data) => {
-                let mut pop = 0;
-                for (i, row) in data.iter_mut().enumerate() {
-                    for (j, cell) in row.iter_mut().enumerate() {
+                for i in 0..LEAF_SIZE {
+                    for j in 0..LEAF_SIZE {
                         let (x, y) = ((j as i64 - x - 1) as usize, (i as i64 - y - 1) as usize);
                         if x < w && y < h {
-                            *cell = grid[y][x];
+                            data.set(j, i, grid[y][x]);
                         }
-                        pop += *cell as u64;
                     }
                 }
-                Node::new_leaf(data, pop)
+                Node::new_leaf(data, data.population())
             }
             NodeKind::Branch(mut children) => {
                 let mut pop = 0;
@@ -613,6 +940,12 @@ impl Universe {
         (self.arena.insert(new_node), new_node.population)
     }
     pub fn set_rect(&mut self, x: i64, y: i64, grid: &Vec<Vec<u8>>) {
+        self.expand_to(
+            x,
+            y,
+            x + grid[0].len() as i64 - 1,
+            y + grid.len() as i64 - 1,
+        );
         let half = 1i64 << (self.level() - 1);
         let (x, y) = (x.clamp(-half, half - 1), y.clamp(-half, half - 1));
         *self.root_mut() = self._set_rect(x, y, grid, self.root()).0;
@@ -633,17 +966,15 @@ impl Universe {
 
         let new_node = match node.data {
             NodeKind::Leaf(mut data) => {
-                let mut pop = 0;
-                for (i, row) in data.iter_mut().enumerate() {
-                    for (j, cell) in row.iter_mut().enumerate() {
+                for i in 0..LEAF_SIZE {
+                    for j in 0..LEAF_SIZE {
                         let (x, y) = (j as i64 - half, i as i64 - half);
                         if x1 <= x && x <= x2 && y1 <= y && y <= y2 {
-                            *cell = 0;
+                            data.set(j, i, 0);
                         }
-                        pop += *cell as u64;
                     }
                 }
-                Node::new_leaf(data, pop)
+                Node::new_leaf(data, data.population())
             }
             NodeKind::Branch(mut children) => {
                 let mut pop = 0;
@@ -692,17 +1023,13 @@ impl Universe {
         };
         match node.data {
             NodeKind::Leaf(data) => {
-                for (y, row) in data.iter().enumerate() {
-                    for (x, cell) in row.iter().enumerate() {
-                        if *cell != 0 {
-                            match bound {
-                                Bound::Top => take(top + y as i64),
-                                Bound::Left => take(left + x as i64),
-                                Bound::Bottom => take(top + y as i64),
-                                Bound::Right => take(left + x as i64),
-                            };
-                        }
-                    }
+                for (x, y, _) in data.iter() {
+                    match bound {
+                        Bound::Top => take(top + y as i64),
+                        Bound::Left => take(left + x as i64),
+                        Bound::Bottom => take(top + y as i64),
+                        Bound::Right => take(left + x as i64),
+                    };
                 }
             }
             NodeKind::Branch(children) => {
@@ -740,41 +1067,12 @@ impl Universe {
             self._get_bound(&Bound::Bottom, self.root(), -h, -h),
         )
     }
-
-    pub fn can_undo(&self) -> bool {
-        self.history_index > 0
-    }
-    pub fn undo(&mut self) {
-        if !self.can_undo() {
-            return;
-        };
-        if !self.can_redo() && self.root() != *self.history.last().unwrap() {
-            self.push_snapshot();
-        }
-        self.history_index -= 1;
-        *self.root_mut() = self.history[self.history_index];
-    }
-    pub fn can_redo(&self) -> bool {
-        self.history_index + 1 < self.history.len()
-    }
-    pub fn redo(&mut self) {
-        if !self.can_redo() {
-            return;
-        }
-        self.history_index += 1;
-        *self.root_mut() = self.history[self.history_index];
-    }
-    pub fn push_snapshot(&mut self) {
-        self.history.truncate(self.history_index + 1);
-        self.history.push(self.root());
-        self.history_index += 1;
-    }
 }
 
 pub struct UniverseIterator<'a> {
     universe: &'a Universe,
     stack: VecDeque<(NodeRef, i64, i64)>,
-    leaf_queue: VecDeque<(i64, i64)>,
+    leaf_queue: VecDeque<(i64, i64, u8)>,
     x1: i64,
     y1: i64,
     x2: i64,
@@ -782,7 +1080,7 @@ pub struct UniverseIterator<'a> {
 }
 
 impl<'a> Iterator for UniverseIterator<'a> {
-    type Item = (i64, i64);
+    type Item = (i64, i64, u8);
     fn next(&mut self) -> Option<Self::Item> {
         if !self.leaf_queue.is_empty() {
             return Some(self.leaf_queue.pop_front().unwrap());
@@ -797,15 +1095,10 @@ impl<'a> Iterator for UniverseIterator<'a> {
 
             match node.data {
                 NodeKind::Leaf(data) => {
-                    for i in 0..LEAF_SIZE * LEAF_SIZE {
-                        let (y, x) = ((i / LEAF_SIZE) as i64, (i % LEAF_SIZE) as i64);
-                        if self.x1 <= left + x
-                            && left + x <= self.x2
-                            && self.y1 <= top + y
-                            && top + y <= self.y2
-                            && data[y as usize][x as usize] != 0
-                        {
-                            self.leaf_queue.push_back((left + x, top + y));
+                    for (x, y, state) in data.iter() {
+                        let (x, y) = (left + x as i64, top + y as i64);
+                        if self.x1 <= x && x <= self.x2 && self.y1 <= y && y <= self.y2 {
+                            self.leaf_queue.push_back((x, y, state));
                         }
                     }
                     if !self.leaf_queue.is_empty() {
@@ -846,3 +1139,142 @@ impl Universe {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    // steps an 8x8 square with step_grid, returning its center 4x4
+    fn step_reference(cells: &[[u8; 8]; 8], generations: usize, rule: &LifeRule) -> [[u8; 4]; 4] {
+        let mut data = vec![vec![0; 10]; 10];
+        for (y, row) in cells.iter().enumerate() {
+            for (x, &state) in row.iter().enumerate() {
+                data[y + 1][x + 1] = state;
+            }
+        }
+        let mut next_data = data.clone();
+        for _ in 0..generations {
+            step_grid(&data, &mut next_data, rule);
+            std::mem::swap(&mut data, &mut next_data);
+        }
+        let mut center = [[0; 4]; 4];
+        for (y, row) in center.iter_mut().enumerate() {
+            for (x, state) in row.iter_mut().enumerate() {
+                *state = data[y + 3][x + 3];
+            }
+        }
+        center
+    }
+
+    fn random_square(rng: &mut StdRng, states: u16) -> [[u8; 8]; 8] {
+        let mut cells = [[0; 8]; 8];
+        for state in cells.iter_mut().flatten() {
+            *state = rng.random_range(0..states) as u8;
+        }
+        cells
+    }
+
+    fn check_leaf_step(rule: &str) {
+        let rule: LifeRule = rule.parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut u = Universe::default();
+        for _ in 0..1000 {
+            let cells = random_square(&mut rng, rule.states());
+            let mut children = [Leaf::default(); 4];
+            for (y, row) in cells.iter().enumerate() {
+                for (x, &state) in row.iter().enumerate() {
+                    let i = 2 * (y / LEAF_SIZE) + x / LEAF_SIZE;
+                    children[i].set(x % LEAF_SIZE, y % LEAF_SIZE, state);
+                }
+            }
+            let children =
+                children.map(|leaf| u.arena.insert(Node::new_leaf(leaf, leaf.population())));
+            let population = children.iter().map(|&c| u.arena.get(c).population).sum();
+            let node = u
+                .arena
+                .insert(Node::new_branch(children, LEAF_LEVEL + 1, population));
+
+            for step in 0..2 {
+                let (next, _) = u.step_node(node, step, &rule);
+                let leaf = u.arena.get(next).data.as_leaf();
+                let expected = step_reference(&cells, 1 << step, &rule);
+                for (y, row) in expected.iter().enumerate() {
+                    for (x, &state) in row.iter().enumerate() {
+                        assert_eq!(leaf.get(x, y), state, "{:?} at step {}", cells, step);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leaf_step_matches_step_grid() {
+        check_leaf_step("B3/S23");
+        check_leaf_step("B2n3-q/S23-a4i");
+        check_leaf_step("23/3/4");
+    }
+
+    #[test]
+    fn step_square_matches_step_grid() {
+        for rule in ["B3/S23", "B2n3-q/S23-a4i"] {
+            let rule: LifeRule = rule.parse().unwrap();
+            let table = leaf_table(&rule);
+            let mut rng = StdRng::seed_from_u64(0);
+            for _ in 0..1000 {
+                let square: u64 = rng.random();
+                let grid = (0..8)
+                    .map(|y| {
+                        (0..8)
+                            .map(|x| ((square >> (8 * y + x)) & 1) as u8)
+                            .collect()
+                    })
+                    .collect::<Vec<Vec<_>>>();
+                let mut expected = grid.clone();
+                step_grid(&grid, &mut expected, &rule);
+                let next = step_square(square, &[0, 2, 4], &table);
+                for y in 1..7 {
+                    for x in 1..7 {
+                        assert_eq!(((next >> (8 * y + x)) & 1) as u8, expected[y][x]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn run(rule: &str, cells: &[(i64, i64)], generations: u64) -> Vec<(i64, i64)> {
+        let mut u = Universe::default();
+        u.rule = rule.parse().unwrap();
+        for &(x, y) in cells {
+            u.set(x, y, 1);
+        }
+        for _ in 0..generations {
+            u.step();
+        }
+        let mut alive = Vec::new();
+        for y in -8..8 {
+            for x in -8..8 {
+                if u.get(x, y) == 1 {
+                    alive.push((x, y));
+                }
+            }
+        }
+        alive
+    }
+
+    #[test]
+    fn isotropic_rules() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let moved = glider.map(|(x, y)| (x + 1, y + 1));
+        let sort = |mut cells: Vec<(i64, i64)>| {
+            cells.sort_by_key(|&(x, y)| (y, x));
+            cells
+        };
+        assert_eq!(run("B2n3/S23-q", &glider, 4), sort(moved.to_vec()));
+
+        // two cells at opposite corners give birth between them only with 2n
+        let pair = [(0, 0), (2, 2)];
+        assert_eq!(run("B2n3/S23-q", &pair, 1), vec![(1, 1)]);
+        assert_eq!(run("B3/S23", &pair, 1), vec![]);
+    }
+}
@@ -3,17 +3,20 @@ use js_sys::wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     Blob, CompressionFormat, CompressionStream, DecompressionStream, HtmlAnchorElement,
-    ReadableWritablePair, Response, Url, window,
+    ReadableWritablePair, Response, Storage, Url, window,
 };
 
 pub fn download_text_file(filename: &str, content: &str) {
+    download_blob(filename, &blob_from_str(content));
+}
+pub fn download_file(filename: &str, data: &[u8]) {
+    download_blob(filename, &blob_from_bytes(data));
+}
+pub fn download_blob(filename: &str, blob: &Blob) {
     let document = window().unwrap().document().unwrap();
     let body = document.body().unwrap();
 
-    let parts = js_sys::Array::of1(&JsValue::from_str(content));
-    let blob = Blob::new_with_str_sequence(parts.as_ref()).unwrap();
-
-    let url = Url::create_object_url_with_blob(&blob).unwrap();
+    let url = Url::create_object_url_with_blob(blob).unwrap();
 
     let a = document
         .create_element("a")
@@ -29,6 +32,11 @@ pub fn download_text_file(filename: &str, content: &str) {
     Url::revoke_object_url(&url).unwrap();
 }
 
+// None when storage is disabled, as in some private windows
+pub fn local_storage() -> Option<Storage> {
+    window()?.local_storage().ok()?
+}
+
 pub fn blob_from_bytes(data: &[u8]) -> Blob {
     let arr = js_sys::Uint8Array::from(data);
     let parts = js_sys::Array::of1(&arr);
@@ -67,7 +75,9 @@ pub async fn base64_gz_from_str(s: &str) -> Result<String, JsValue> {
     Ok(BASE64_URL_SAFE.encode(compressed))
 }
 pub async fn str_from_base64_gz(base64: &str) -> Result<String, JsValue> {
-    let bytes = BASE64_URL_SAFE.decode(base64).unwrap();
+    let bytes = BASE64_URL_SAFE
+        .decode(base64)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
     let decompressed = decompress_gz(&bytes).await?;
     Ok(String::from_utf8_lossy(&decompressed).to_string())
 }